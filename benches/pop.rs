@@ -0,0 +1,44 @@
+//! Benchmarks for aggregates relying upon proofs-of-possession,
+//! which we keep out of the unit tests so that `cargo test` stays fast.
+//!
+//! Run these with `cargo +nightly bench`.
+
+#![feature(test)]
+
+extern crate bls_like;
+extern crate rand;
+extern crate test;
+
+use bls_like::*;
+use bls_like::pop::AggregatedBySameMessage;
+use rand::thread_rng;
+use test::Bencher;
+
+fn same_message_aggregate(signers: usize) -> AggregatedBySameMessage<ZBLS> {
+    let context = Context::new(b"ctx");
+    let message = Message::new(&context,b"consensus round");
+    let mut agg = AggregatedBySameMessage::<ZBLS>::new(message);
+    for _ in 0..signers {
+        let mut keypair = Keypair::<ZBLS>::generate(thread_rng());
+        agg.add(&keypair.sign(&context,b"consensus round")).unwrap();
+    }
+    agg
+}
+
+#[bench]
+fn bench_same_message_1(b: &mut Bencher) {
+    let agg = same_message_aggregate(1);
+    b.iter(|| assert!( agg.verify() ));
+}
+
+#[bench]
+fn bench_same_message_16(b: &mut Bencher) {
+    let agg = same_message_aggregate(16);
+    b.iter(|| assert!( agg.verify() ));
+}
+
+#[bench]
+fn bench_same_message_256(b: &mut Bencher) {
+    let agg = same_message_aggregate(256);
+    b.iter(|| assert!( agg.verify() ));
+}
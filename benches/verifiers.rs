@@ -0,0 +1,55 @@
+//! Benchmarks for the verifiers that need many signatures, which we
+//! keep out of the unit tests so that `cargo test` stays fast.
+//!
+//! Run these with `cargo +nightly bench`.
+
+#![feature(test)]
+
+extern crate bls_like;
+extern crate rand;
+extern crate test;
+
+use bls_like::*;
+use bls_like::single::SignedMessage;
+use bls_like::verifiers::*;
+use rand::thread_rng;
+use test::Bencher;
+
+fn sign_many<E: UnmutatedKeys>(signers: usize, messages: usize) -> Vec<SignedMessage<E>> {
+    let mut keypairs = (0..signers).map(|_| Keypair::<E>::generate(thread_rng())).collect::<Vec<_>>();
+    let context = Context::new(b"ctx");
+    (0..messages).map(|i| {
+        keypairs[i % signers].sign(&context,&(i as u64).to_le_bytes())
+    }).collect()
+}
+
+#[bench]
+fn bench_batch(b: &mut Bencher) {
+    let signed = sign_many::<ZBLS>(100,100);
+    b.iter(|| assert!( verify_batch(signed.iter(), thread_rng()) ));
+}
+
+#[bench]
+fn bench_batch_unbatched(b: &mut Bencher) {
+    let signed = sign_many::<ZBLS>(100,100);
+    b.iter(|| assert!( signed.iter().all(verify_simple) ));
+}
+
+fn independent_aggregates(n: usize) -> Vec<distinct::DistinctMessages<ZBLS>> {
+    (0..n).map(|_| {
+        sign_many::<ZBLS>(2,4).iter()
+            .try_fold(distinct::DistinctMessages::new(), |dm,s| dm.add(s)).unwrap()
+    }).collect()
+}
+
+#[bench]
+fn bench_verify_all(b: &mut Bencher) {
+    let dms = independent_aggregates(100);
+    b.iter(|| assert!( verify_all(dms.iter()).iter().all(|r| *r) ));
+}
+
+#[bench]
+fn bench_verify_all_unbatched(b: &mut Bencher) {
+    let dms = independent_aggregates(100);
+    b.iter(|| assert!( dms.iter().all(|dm| dm.verify()) ));
+}
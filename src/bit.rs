@@ -11,6 +11,8 @@ use pairing::{CurveProjective}; // CurveAffine, Engine
 
 use super::*;
 use super::single::SignedMessage;
+#[cfg(feature = "parallel")]
+use super::pop::sum_publickeys;
 use super::verifiers::verify_with_distinct_messages;
 
 
@@ -74,6 +76,17 @@ where E: EngineBLS, ST: SignerTable<E>
     })
 }
 
+/// Indices bit mask for `self.signers[offset]` occupied by the identity
+/// public key, which verifies on any message without any secret key.
+fn chunk_identities<E,ST>(signer_table: &ST, offset: usize) -> u8 
+where E: EngineBLS, ST: SignerTable<E>
+{
+    (0..8).into_iter().fold(0u8, |b,j| {
+        let identity = signer_table.lookup(8*offset + j).is_some_and(|pk| pk.0.is_zero());
+        if identity { b | 1u8 << j } else { b }
+    })
+}
+
 /// Avoiding duplicate keys inside a slice gets costly.  We suggest
 /// improving performance by using a customized data type.
 ///
@@ -128,6 +141,12 @@ pub enum SignerTableError {
     /// Aggregation is impossible due to signers being repeated or
     /// repeated too many times in both sets or multi-sets, respectively.
     RepeatedSigners,
+    /// Signer indices were not strictly increasing, so some signer
+    /// was either repeated or presented out of order.
+    NonMonotonicIndices,
+    /// Attempted to aggregate a contribution by the identity public key,
+    /// which verifies on any message without any secret key.
+    IdentityPublicKey,
 }
 
 impl ::std::fmt::Display for SignerTableError {
@@ -137,6 +156,8 @@ impl ::std::fmt::Display for SignerTableError {
             BadSignerTable(s) => write!(f, "{}", s),
             MismatchedMessage => write!(f, "Cannot aggregate distinct messages with only a bit field."),
             RepeatedSigners => write!(f, "Cannot aggregate due to duplicate signers."),
+            NonMonotonicIndices => write!(f, "Signer indices must be strictly increasing."),
+            IdentityPublicKey => write!(f, "Attempted to aggregate the identity public key."),
         }
     }
 }
@@ -148,6 +169,8 @@ impl ::std::error::Error for SignerTableError {
             BadSignerTable(s) => s,
             MismatchedMessage => "Cannot aggregate distinct messages with only a bit field.",
             RepeatedSigners => "Cannot aggregate due to duplicate signers",
+            NonMonotonicIndices => "Signer indices must be strictly increasing.",
+            IdentityPublicKey => "Attempted to aggregate the identity public key.",
         }
     }
 }
@@ -199,6 +222,12 @@ where
     type PKnM = ::std::iter::Once<(Message, PublicKey<E>)>;
 
     fn messages_and_publickeys(self) -> Self::PKnM {
+        // We look up signers serially because `SignerTable` need not
+        // be `Sync`, but `sum_publickeys` may add them in parallel.
+        // Without `parallel`, we sum in place and allocate nothing.
+        #[cfg(feature = "parallel")]
+        let mut publickeys = Vec::new();
+        #[cfg(not(feature = "parallel"))]
         let mut publickey = E::PublicKeyGroup::zero();
         for i in 0..8*self.signers.borrow().len() {
             if self.signers.borrow()[i / 8] & (1 << (i % 8)) != 0 {
@@ -208,10 +237,17 @@ where
                     debug_assert!(false, "Incorrect SignerTable implementation with duplicate publickeys" );
                     continue;
                 }
+                #[cfg(feature = "parallel")]
+                publickeys.push(pop_pk);
+                #[cfg(not(feature = "parallel"))]
                 publickey.add_assign(&pop_pk.0);
             }
         }
-        once((self.message.clone(), PublicKey(publickey)))
+        #[cfg(feature = "parallel")]
+        let publickey = sum_publickeys(&publickeys);
+        #[cfg(not(feature = "parallel"))]
+        let publickey = PublicKey(publickey);
+        once((self.message, publickey))
     }
 
     fn signature(&self) -> Signature<E> { self.signature }
@@ -237,7 +273,43 @@ where
         BitSignedMessage { proofs_of_possession, signers, message, signature }
     }
 
+    /// Reconstruct a `BitSignedMessage` from its signers bitfield,
+    /// like one received via gossip, after checking that the bitfield
+    /// matches the `SignerTable` in length and names only occupied
+    /// positions.  See `signers` for the bit order.
+    pub fn from_signers(proofs_of_possession: POP, message: Message, signers: &[u8], signature: Signature<E>)
+     -> Result<BitSignedMessage<E,POP>,SignerTableError>
+    {
+        let mut bitsig = BitSignedMessage::new(proofs_of_possession, message);
+        if signers.len() != bitsig.signers.borrow().len() {
+            return Err(SignerTableError::BadSignerTable("Signers bitfield length mismatched"));
+        }
+        for (offset,s) in signers.iter().enumerate() {
+            if *s & ! chunk_lookups(&bitsig.proofs_of_possession, offset) != 0 {
+                return Err(SignerTableError::BadSignerTable("Absent signer"));
+            }
+            if *s & chunk_identities(&bitsig.proofs_of_possession, offset) != 0 {
+                return Err(SignerTableError::IdentityPublicKey);
+            }
+        }
+        bitsig.signers.borrow_mut().copy_from_slice(signers);
+        bitsig.signature = signature;
+        Ok(bitsig)
+    }
+
+    /// Return our signers as a bitfield, suitable for gossiping who signed.
+    ///
+    /// Bit `i % 8` of byte `i / 8` is set if the signer at index `i`
+    /// of our `SignerTable` signed, so receivers must agree upon the
+    /// `SignerTable` ordering to interpret this bitfield.
+    pub fn signers(&self) -> &[u8] {
+        self.signers.borrow()
+    }
+
     fn add_points(&mut self, publickey: PublicKey<E>, signature: Signature<E>) -> Result<(),SignerTableError> {
+        if publickey.0.is_zero() {
+            return Err(SignerTableError::IdentityPublicKey);
+        }
         let i = self.proofs_of_possession.find(&publickey)
             .ok_or(SignerTableError::BadSignerTable("Mismatched proof-of-possession")) ?;
         if self.proofs_of_possession.lookup(i) != Some(publickey) {
@@ -263,6 +335,10 @@ where
 
     /// Merge two `BitSignedMessage`, after testing for message
     /// and proofs-of-possession table agreement, and disjoint publickeys.
+    ///
+    /// We union the signers bitfields and add the signatures, but
+    /// reject any signer present in both, leaving `self` unchanged,
+    /// so we never count one public key twice.
     pub fn merge(&mut self, other: &BitSignedMessage<E,POP>) -> Result<(),SignerTableError> {
         if self.message != other.message {
             return Err(SignerTableError::MismatchedMessage);
@@ -275,6 +351,9 @@ where
             if *y & ! chunk_lookups(&self.proofs_of_possession, offset) != 0 {
                 return Err(SignerTableError::BadSignerTable("Absent signer"));
             }
+            if *y & chunk_identities(&self.proofs_of_possession, offset) != 0 {
+                return Err(SignerTableError::IdentityPublicKey);
+            }
         }
         for (x,y) in self.signers.borrow_mut().iter_mut().zip(other.signers.borrow()) {
             *x |= y;
@@ -438,6 +517,9 @@ where
 
 
     fn add_points(&mut self, publickey: PublicKey<E>, signature: Signature<E>) -> Result<(),SignerTableError> {
+        if publickey.0.is_zero() {
+            return Err(SignerTableError::IdentityPublicKey);
+        }
         let i = self.proofs_of_possession.find(&publickey)
             .ok_or(SignerTableError::BadSignerTable("Mismatched proof-of-possession")) ?;
         if self.proofs_of_possession.lookup(i) != Some(publickey) {
@@ -475,6 +557,9 @@ where
             {
                 return Err(SignerTableError::BadSignerTable("Absent signer"));
             }
+            if os[offset] & chunk_identities(&self.proofs_of_possession, offset) != 0u8 {
+                return Err(SignerTableError::IdentityPublicKey);
+            }
             for j in 0..8 {
                 let mut count = self.get_count(8*offset+j);
                 if os[offset] & (1 << j) != 0 { count += 1; }
@@ -489,6 +574,31 @@ where
         Ok(())
     }
 
+    /// Count the distinct signers who contributed, counting a signer
+    /// only once even if we aggregated their contribution repeatedly.
+    pub fn signer_count(&self) -> usize {
+        let mut any = Vec::new();
+        for signers in self.signers.iter().map(|signers| signers.borrow()) {
+            any.resize(signers.len(), 0u8);
+            for (a,s) in any.iter_mut().zip(signers) { *a |= *s; }
+        }
+        any.iter().map(|a| a.count_ones() as usize).sum()
+    }
+
+    /// Check that at least `threshold` distinct signers contributed,
+    /// without checking the signature.
+    pub fn meets_threshold(&self, threshold: usize) -> bool {
+        self.signer_count() >= threshold
+    }
+
+    /// Verify the signature only if at least `threshold` distinct
+    /// signers contributed, so verifiers never compute pairings for
+    /// aggregates already below quorum.  A `threshold` of zero still
+    /// requires a valid signature.
+    pub fn verify_threshold(&self, threshold: usize) -> bool {
+        self.meets_threshold(threshold) && self.verify()
+    }
+
     /// Merge two `CountSignedMessage`, after testing for message
     /// and proofs-of-possession table agreement, and disjoint publickeys.
     pub fn merge(&mut self, other: &CountSignedMessage<E,POP>) -> Result<(),SignerTableError> {
@@ -504,6 +614,11 @@ where
             {
                 return Err(SignerTableError::BadSignerTable("Absent signer"));
             }
+            if other.signers.iter().fold(0u8, |b,s| b | s.borrow()[offset])
+               & chunk_identities(&self.proofs_of_possession, offset) != 0u8
+            {
+                return Err(SignerTableError::IdentityPublicKey);
+            }
             for j in 0..8 {
                 let index = 8*offset+j;
                 self.test_count( self.get_count(index).saturating_add(other.get_count(index)) )?;
@@ -518,6 +633,166 @@ where
 }
 
 
+/// One individual message with attached aggreggate BLS signatures
+/// from signers for whom we previously checked proofs-of-possession,
+/// and with the singers presented as a sorted list of indices.
+///
+/// We may aggregage only one signatures per signer here, like
+/// `BitSignedMessage`, but we store one index per signer instead of
+/// one bit per `SignerTable` entry, which works better when only a
+/// few members of a large signer table sign.
+///
+/// We require the indices be strictly increasing, so that no signer
+/// gets counted twice and every signer set has exactly one
+/// representation.  We enforce this both when building the aggregate,
+/// in `add` and `from_indices`, and again during verification.
+pub struct IndexSignedMessage<E: EngineBLS, POP: SignerTable<E>> {
+    proofs_of_possession: POP,
+    signers: Vec<usize>,
+    message: Message,
+    signature: Signature<E>,
+}
+
+impl<E,POP> Clone for IndexSignedMessage<E,POP>
+where 
+    E: EngineBLS,
+    POP: SignerTable<E>+Clone,
+{
+    fn clone(&self) -> IndexSignedMessage<E,POP> {
+        IndexSignedMessage {
+            proofs_of_possession: self.proofs_of_possession.clone(),
+            signers: self.signers.clone(),
+            message: self.message,
+            signature: self.signature,
+        }
+    }
+}
+
+impl<E,POP> Signed for &IndexSignedMessage<E,POP> 
+where
+    E: EngineBLS,
+    POP: SignerTable<E>,
+{
+    type E = E;
+
+    type M = Message;
+    type PKG = PublicKey<E>;
+
+    type PKnM = ::std::iter::Once<(Message, PublicKey<E>)>;
+
+    fn messages_and_publickeys(self) -> Self::PKnM {
+        #[cfg(feature = "parallel")]
+        let mut publickeys = Vec::with_capacity(self.signers.len());
+        #[cfg(not(feature = "parallel"))]
+        let mut publickey = E::PublicKeyGroup::zero();
+        for i in self.signers.iter().cloned() {
+            let pop_pk = match self.proofs_of_possession.lookup(i) {
+                Some(pk) => pk,
+                None => {
+                    // unreachable due to checks in add and from_indices
+                    debug_assert!(false, "Absent signer index" );
+                    continue;
+                }
+            };
+            if Some(i) != self.proofs_of_possession.find(&pop_pk) {
+                // unreachable due to check in add and from_indices
+                debug_assert!(false, "Incorrect SignerTable implementation with duplicate publickeys" );
+                continue;
+            }
+            #[cfg(feature = "parallel")]
+            publickeys.push(pop_pk);
+            #[cfg(not(feature = "parallel"))]
+            publickey.add_assign(&pop_pk.0);
+        }
+        #[cfg(feature = "parallel")]
+        let publickey = sum_publickeys(&publickeys);
+        #[cfg(not(feature = "parallel"))]
+        let publickey = PublicKey(publickey);
+        once((self.message, publickey))
+    }
+
+    fn signature(&self) -> Signature<E> { self.signature }
+
+    fn verify(self) -> bool {
+        // We recheck index monotonicity here, despite `add` and
+        // `from_indices` enforcing it, because a malformed aggregate
+        // must never reach the pairing, and this scan costs nothing
+        // by comparison.
+        if self.check_indices().is_err() { return false; }
+        verify_with_distinct_messages(self,true)
+    }
+}
+
+impl<E,POP> IndexSignedMessage<E,POP> 
+where
+    E: EngineBLS,
+    POP: SignerTable<E>,
+{
+    pub fn new(proofs_of_possession: POP, message: Message) -> IndexSignedMessage<E,POP> {
+        let signers = Vec::new();
+        let signature = Signature(E::SignatureGroup::zero());
+        IndexSignedMessage { proofs_of_possession, signers, message, signature }
+    }
+
+    /// Reconstruct an aggregate from its signer indices and signature,
+    /// after testing that the indices are strictly increasing and
+    /// all occupied in our proofs-of-possession table.
+    pub fn from_indices(proofs_of_possession: POP, message: Message, signers: Vec<usize>, signature: Signature<E>)
+     -> Result<IndexSignedMessage<E,POP>,SignerTableError>
+    {
+        let me = IndexSignedMessage { proofs_of_possession, signers, message, signature };
+        me.check_indices() ?;
+        for i in me.signers.iter().cloned() {
+            let pk = me.proofs_of_possession.lookup(i)
+                .ok_or(SignerTableError::BadSignerTable("Absent signer")) ?;
+            if me.proofs_of_possession.find(&pk) != Some(i) {
+                return Err(SignerTableError::BadSignerTable("Invalid SignerTable implementation with missmatched lookups"));
+            }
+            if pk.0.is_zero() {
+                return Err(SignerTableError::IdentityPublicKey);
+            }
+        }
+        Ok(me)
+    }
+
+    /// Signer indices in strictly increasing order.
+    pub fn signers(&self) -> &[usize] {
+        &self.signers
+    }
+
+    /// Test that our signer indices are strictly increasing.
+    pub fn check_indices(&self) -> Result<(),SignerTableError> {
+        if self.signers.windows(2).all(|w| w[0] < w[1]) {
+            Ok(())
+        } else {
+            Err(SignerTableError::NonMonotonicIndices)
+        }
+    }
+
+    /// Include one signed message, after testing for message and
+    /// proofs-of-possession table agreement, and disjoint publickeys.
+    pub fn add(&mut self, signed: &SignedMessage<E>) -> Result<(),SignerTableError>
+    {
+        if self.message != signed.message {
+            return Err(SignerTableError::MismatchedMessage);
+        }
+        if signed.publickey.0.is_zero() {
+            return Err(SignerTableError::IdentityPublicKey);
+        }
+        let i = self.proofs_of_possession.find(&signed.publickey)
+            .ok_or(SignerTableError::BadSignerTable("Mismatched proof-of-possession")) ?;
+        if self.proofs_of_possession.lookup(i) != Some(signed.publickey) {
+            return Err(SignerTableError::BadSignerTable("Invalid SignerTable implementation with missmatched lookups"));
+        }
+        match self.signers.binary_search(&i) {
+            Ok(_) => return Err(SignerTableError::RepeatedSigners),
+            Err(position) => self.signers.insert(position,i),
+        }
+        self.signature.0.add_assign(&signed.signature.0);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rand::{thread_rng};  // Rng
@@ -526,15 +801,16 @@ mod tests {
 
     #[test]
     fn proofs_of_possession() {
-        let msg1 = Message::new(b"ctx",b"some message");
-        let msg2 = Message::new(b"ctx",b"another message");
+        let context = Context::new(b"ctx");
+        let msg1 = Message::new(&context,b"some message");
+        let msg2 = Message::new(&context,b"another message");
 
         let k = |_| Keypair::<ZBLS>::generate(thread_rng());
         let mut keypairs = (0..4).into_iter().map(k).collect::<Vec<_>>();
         let pop = keypairs.iter().map(|k| k.public).collect::<Vec<_>>();
         let dup = keypairs[3].clone();
         keypairs.push(dup);
-        let sigs1 = keypairs.iter_mut().map(|k| k.sign(msg1)).collect::<Vec<_>>();
+        let sigs1 = keypairs.iter_mut().map(|k| k.sign(&context,b"some message")).collect::<Vec<_>>();
 
         let mut bitsig1 = BitSignedMessage::<ZBLS,_>::new(pop.clone(),msg1);
         assert!( bitsig1.verify() );  // verifiers::verify_with_distinct_messages(&dms,true)
@@ -554,7 +830,7 @@ mod tests {
         assert!( verifiers::verify_with_distinct_messages(&bitsig1,false) );
         // assert!( verifiers::verify_with_gaussian_elimination(&dms) );
 
-        let sigs2 = keypairs.iter_mut().map(|k| k.sign(msg2)).collect::<Vec<_>>();  
+        let sigs2 = keypairs.iter_mut().map(|k| k.sign(&context,b"another message")).collect::<Vec<_>>();  
         let mut bitsig2 = BitSignedMessage::<ZBLS,_>::new(pop.clone(),msg2);
         for sig in sigs2.iter().take(3) {
             assert!( bitsig2.add(sig).is_ok() );
@@ -562,14 +838,14 @@ mod tests {
         assert!( bitsig1.merge(&bitsig2).is_err() );
 
         let mut multimsg = pop::BatchAssumingProofsOfPossession::<ZBLS>::new();
-        multimsg.aggregate(&bitsig1);
-        multimsg.aggregate(&bitsig2);
+        assert!( multimsg.aggregate(&bitsig1).is_ok() );
+        assert!( multimsg.aggregate(&bitsig2).is_ok() );
         assert!( multimsg.verify() );  // verifiers::verify_with_distinct_messages(&dms,true)
         assert!( verifiers::verify_unoptimized(&multimsg) );
         assert!( verifiers::verify_simple(&multimsg) );
         assert!( verifiers::verify_with_distinct_messages(&multimsg,false) );
 
-        let oops = Keypair::<ZBLS>::generate(thread_rng()).sign(msg2);
+        let oops = Keypair::<ZBLS>::generate(thread_rng()).sign(&context,b"another message");
         assert!( bitsig1.add_points(oops.publickey,oops.signature).is_err() );
         /*
         TODO: Test that adding signers for an incorrect message fails, but this version angers teh borrow checker.
@@ -608,4 +884,202 @@ mod tests {
         countsig.max_duplicates = 4;
         assert!( countsig.merge(&countpop2).is_err() );
     }
+
+    #[test]
+    fn merge_bitfields() {
+        let context = Context::new(b"ctx");
+        let msg = Message::new(&context,b"some message");
+
+        let k = |_| Keypair::<ZBLS>::generate(thread_rng());
+        let mut keypairs = (0..10).into_iter().map(k).collect::<Vec<_>>();
+        let pop = keypairs.iter().map(|k| k.public).collect::<Vec<_>>();
+        let sigs = keypairs.iter_mut().map(|k| k.sign(&context,b"some message")).collect::<Vec<_>>();
+
+        let aggregate = |range: ::std::ops::Range<usize>| {
+            let mut bitsig = BitSignedMessage::<ZBLS,_>::new(pop.clone(),msg);
+            for sig in sigs[range].iter() { bitsig.add(sig).unwrap(); }
+            bitsig
+        };
+        let full = aggregate(0..10);
+        let mut merged = aggregate(0..5);
+        assert!( merged.merge(&aggregate(5..10)).is_ok() );
+        assert!( merged.signers() == full.signers() );
+        assert!( merged.signers() == &[0xffu8, 0x03][..] );
+        assert!( merged.signature == full.signature );
+        assert!( merged.verify() );
+
+        let mut overlapping = aggregate(0..6);
+        let before = overlapping.clone();
+        assert!( overlapping.merge(&aggregate(5..10)).is_err() );
+        assert!( overlapping.signers() == before.signers() );
+        assert!( overlapping.signature == before.signature );
+
+        // Gossip the bitfield and signature, and reconstruct
+        let half = aggregate(3..7);
+        let received = BitSignedMessage::<ZBLS,_>::from_signers(pop.clone(), msg, half.signers(), half.signature).unwrap();
+        assert!( received.signers() == &[0x78u8, 0x00][..] );
+        assert!( received.verify() );
+        assert!( BitSignedMessage::<ZBLS,_>::from_signers(pop.clone(), msg, &[0x78u8], half.signature).is_err() );
+        assert!( BitSignedMessage::<ZBLS,_>::from_signers(pop.clone(), msg, &[0x78u8, 0x04], half.signature).is_err() );
+    }
+
+    /// Signer table that counts accesses, so we observe verification.
+    struct CountingTable {
+        publickeys: Vec<PublicKey<ZBLS>>,
+        accesses: ::std::cell::Cell<usize>,
+    }
+
+    impl ::std::ops::Deref for CountingTable {
+        type Target = [PublicKey<ZBLS>];
+        fn deref(&self) -> &[PublicKey<ZBLS>] {
+            self.accesses.set(self.accesses.get() + 1);
+            &self.publickeys
+        }
+    }
+
+    #[test]
+    fn count_threshold() {
+        let context = Context::new(b"ctx");
+        let msg = Message::new(&context,b"some message");
+
+        let k = |_| Keypair::<ZBLS>::generate(thread_rng());
+        let mut keypairs = (0..4).into_iter().map(k).collect::<Vec<_>>();
+        let publickeys = keypairs.iter().map(|k| k.public).collect::<Vec<_>>();
+        let sigs = keypairs.iter_mut().map(|k| k.sign(&context,b"some message")).collect::<Vec<_>>();
+        let pop = CountingTable { publickeys, accesses: ::std::cell::Cell::new(0) };
+
+        let mut countsig = CountSignedMessage::<ZBLS,_>::new(pop,msg);
+        assert!( countsig.signer_count() == 0 );
+        assert!( countsig.verify_threshold(0) );
+        assert!( ! countsig.verify_threshold(1) );
+        for sig in [&sigs[0], &sigs[2], &sigs[2], &sigs[2]].iter() {
+            assert!( countsig.add(sig).is_ok() );
+        }
+        assert!( countsig.signer_count() == 2 );
+        assert!( countsig.meets_threshold(2) && ! countsig.meets_threshold(3) );
+        assert!( countsig.verify_threshold(0) );
+        assert!( countsig.verify_threshold(2) );
+        assert!( ! countsig.verify_threshold(3) );
+
+        // Below quorum with an invalid signature, we never touch signers.
+        countsig.signature = sigs[1].signature;
+        let accesses = countsig.proofs_of_possession.accesses.get();
+        assert!( ! countsig.verify_threshold(3) );
+        assert!( countsig.proofs_of_possession.accesses.get() == accesses );
+        // Meeting quorum with an invalid signature still fails.
+        assert!( ! countsig.verify_threshold(2) );
+        assert!( ! countsig.verify_threshold(0) );
+        assert!( countsig.proofs_of_possession.accesses.get() > accesses );
+    }
+
+    #[test]
+    fn non_monotonic_indices() {
+        let context = Context::new(b"ctx");
+        let msg = Message::new(&context,b"some message");
+
+        let k = |_| Keypair::<ZBLS>::generate(thread_rng());
+        let mut keypairs = (0..4).into_iter().map(k).collect::<Vec<_>>();
+        let pop = keypairs.iter().map(|k| k.public).collect::<Vec<_>>();
+        let sigs = keypairs.iter_mut().map(|k| k.sign(&context,b"some message")).collect::<Vec<_>>();
+
+        let mut outsider = Keypair::<ZBLS>::generate(thread_rng());
+        let outsider_sig = outsider.sign(&context,b"some message");
+
+        let mut indexsig = IndexSignedMessage::<ZBLS,_>::new(pop.clone(),msg);
+        for sig in sigs.iter().rev().take(3) {
+            assert!( indexsig.add(sig).is_ok() );
+        }
+        let signature = indexsig.signature;
+
+        // `add` itself rejects duplicates and unknown signers, leaving
+        // the aggregate untouched, before any verification happens.
+        match indexsig.add(&sigs[3]) {
+            Err(SignerTableError::RepeatedSigners) => {},
+            _ => panic!("Duplicate signer index was accepted by add"),
+        }
+        match indexsig.add(&outsider_sig) {
+            Err(SignerTableError::BadSignerTable(_)) => {},
+            _ => panic!("Signer outside the table was accepted by add"),
+        }
+        assert!( indexsig.signers() == &[1,2,3][..] );
+        assert!( indexsig.signature == signature );
+        assert!( indexsig.verify() );
+        assert!( verifiers::verify_simple(&indexsig) );
+
+        let rebuilt = IndexSignedMessage::<ZBLS,_>::from_indices(pop.clone(),msg,vec![1,2,3],indexsig.signature);
+        assert!( rebuilt.unwrap().verify() );
+        let out_of_range = IndexSignedMessage::<ZBLS,_>::from_indices(pop.clone(),msg,vec![1,2,4],indexsig.signature);
+        match out_of_range {
+            Err(SignerTableError::BadSignerTable(_)) => {},
+            _ => panic!("Out of range signer index was accepted"),
+        }
+        let repeated = IndexSignedMessage::<ZBLS,_>::from_indices(pop.clone(),msg,vec![1,2,2,3],indexsig.signature);
+        match repeated {
+            Err(SignerTableError::NonMonotonicIndices) => {},
+            _ => panic!("Repeated signer index was accepted"),
+        }
+        let reordered = IndexSignedMessage::<ZBLS,_>::from_indices(pop.clone(),msg,vec![2,1,3],indexsig.signature);
+        match reordered {
+            Err(SignerTableError::NonMonotonicIndices) => {},
+            _ => panic!("Decreasing signer indices were accepted"),
+        }
+
+        // Corrupt the indices directly, bypassing the codec-level checks.
+        let mut repeated = indexsig.clone();
+        repeated.signers = vec![1,2,2,3];
+        repeated.signature.0.add_assign(&sigs[2].signature.0);
+        match repeated.check_indices() {
+            Err(SignerTableError::NonMonotonicIndices) => {},
+            _ => panic!("Repeated signer index was accepted"),
+        }
+        assert!( ! repeated.verify() );
+
+        let mut decreasing = indexsig.clone();
+        decreasing.signers = vec![3,1,2];
+        assert!( decreasing.check_indices().is_err() );
+        assert!( ! decreasing.verify() );
+    }
+
+    #[test]
+    fn identity_publickey() {
+        let context = Context::new(b"ctx");
+        let msg = Message::new(&context,b"some message");
+
+        let mut keypair = Keypair::<ZBLS>::generate(thread_rng());
+        let signed = keypair.sign(&context,b"some message");
+        // The identity public key verifies the identity signature on any message.
+        let forged = SignedMessage::<ZBLS> {
+            message: msg,
+            publickey: PublicKey(<ZBLS as EngineBLS>::PublicKeyGroup::zero()),
+            signature: Signature(<ZBLS as EngineBLS>::SignatureGroup::zero()),
+        };
+        assert!( forged.verify() );
+        let pop = vec![keypair.public, forged.publickey];
+        let is_identity = |r: Result<(),SignerTableError>| matches!(r, Err(SignerTableError::IdentityPublicKey));
+
+        let mut bitsig = BitSignedMessage::<ZBLS,_>::new(pop.clone(),msg);
+        assert!( bitsig.add(&signed).is_ok() );
+        assert!( is_identity(bitsig.add(&forged)) );
+        assert!( is_identity(bitsig.add_points(forged.publickey,forged.signature)) );
+        assert!( is_identity(BitSignedMessage::<ZBLS,_>::from_signers(pop.clone(),msg,&[2u8],forged.signature).map(|_| ())) );
+        let mut forged_bitsig = BitSignedMessage::<ZBLS,_>::new(pop.clone(),msg);
+        forged_bitsig.signers[0] = 2u8;
+        assert!( is_identity(bitsig.merge(&forged_bitsig)) );
+        assert!( bitsig.signers() == &[1u8][..] && bitsig.verify() );
+
+        let mut countsig = CountSignedMessage::<ZBLS,_>::new(pop.clone(),msg);
+        assert!( countsig.add(&signed).is_ok() );
+        assert!( is_identity(countsig.add(&forged)) );
+        assert!( is_identity(countsig.add_bitsig(&forged_bitsig)) );
+        let mut forged_countsig = CountSignedMessage::<ZBLS,_>::new(pop.clone(),msg);
+        forged_countsig.signers[0][0] = 2u8;
+        assert!( is_identity(countsig.merge(&forged_countsig)) );
+        assert!( countsig.signer_count() == 1 && countsig.verify() );
+
+        let mut indexsig = IndexSignedMessage::<ZBLS,_>::new(pop.clone(),msg);
+        assert!( indexsig.add(&signed).is_ok() );
+        assert!( is_identity(indexsig.add(&forged)) );
+        assert!( is_identity(IndexSignedMessage::<ZBLS,_>::from_indices(pop.clone(),msg,vec![0,1],signed.signature).map(|_| ())) );
+        assert!( indexsig.signers() == &[0][..] && indexsig.verify() );
+    }
 }
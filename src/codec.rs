@@ -0,0 +1,154 @@
+//! ## Pluggable curve point serialization conventions
+//!
+//! We abstract point serialization behind the `PointCodec` trait,
+//! so that `Signature` and `PublicKey` can be encoded following
+//! whatever conventions some ecosystem expects, via their `encode`
+//! and `decode` methods.
+//!
+//! We provide `ZCashCodec`, which employs the pairing crate's own
+//! encoding for BLS12-381:  Coordinates are big endian, and the
+//! three most significant bits of the first byte flag compression,
+//! the point at infinity, and the lexicographically larger `y`
+//! coordinate, respectively.
+//!
+//! We also provide `Eth2Codec`, but Ethereum 2.0 adopted the ZCash
+//! encoding unchanged, as did Chia, so `Eth2Codec` presently agrees
+//! with `ZCashCodec` byte for byte.  We keep it seperate so that
+//! code naming its ecosystem explicitly need not change if either
+//! convention ever diverges.  Any genuinely different conventions
+//! should be added as further `PointCodec` implementations.
+
+use pairing::{CurveAffine, EncodedPoint, GroupDecodingError};
+
+
+/// Serialization conventions for affine curve points.
+pub trait PointCodec {
+    /// Encode a point in compressed form.
+    fn encode_compressed<G: CurveAffine>(point: &G) -> Vec<u8>;
+
+    /// Decode a point from compressed form, checking that it lies
+    /// on the curve and inside the prime order subgroup.
+    fn decode_compressed<G: CurveAffine>(bytes: &[u8]) -> Result<G,GroupDecodingError>;
+
+    /// Encode a point in uncompressed form.
+    fn encode_uncompressed<G: CurveAffine>(point: &G) -> Vec<u8>;
+
+    /// Decode a point from uncompressed form, checking that it lies
+    /// on the curve and inside the prime order subgroup.
+    fn decode_uncompressed<G: CurveAffine>(bytes: &[u8]) -> Result<G,GroupDecodingError>;
+}
+
+fn decode_encoded_point<P: EncodedPoint>(bytes: &[u8]) -> Result<P::Affine,GroupDecodingError> {
+    let mut encoded = P::empty();
+    if bytes.len() != encoded.as_mut().len() {
+        // We should ideally return our own error here, but this seems acceptable for now.
+        return Err(GroupDecodingError::UnexpectedInformation);
+    }
+    encoded.as_mut().copy_from_slice(bytes);
+    encoded.into_affine()
+}
+
+/// Point serialization used by ZCash and the pairing crate.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct ZCashCodec;
+
+impl PointCodec for ZCashCodec {
+    fn encode_compressed<G: CurveAffine>(point: &G) -> Vec<u8> {
+        point.into_compressed().as_ref().to_vec()
+    }
+
+    fn decode_compressed<G: CurveAffine>(bytes: &[u8]) -> Result<G,GroupDecodingError> {
+        decode_encoded_point::<G::Compressed>(bytes)
+    }
+
+    fn encode_uncompressed<G: CurveAffine>(point: &G) -> Vec<u8> {
+        point.into_uncompressed().as_ref().to_vec()
+    }
+
+    fn decode_uncompressed<G: CurveAffine>(bytes: &[u8]) -> Result<G,GroupDecodingError> {
+        decode_encoded_point::<G::Uncompressed>(bytes)
+    }
+}
+
+/// Point serialization used by Ethereum 2.0, which presently
+/// agrees with `ZCashCodec`.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct Eth2Codec;
+
+impl PointCodec for Eth2Codec {
+    fn encode_compressed<G: CurveAffine>(point: &G) -> Vec<u8> {
+        ZCashCodec::encode_compressed(point)
+    }
+
+    fn decode_compressed<G: CurveAffine>(bytes: &[u8]) -> Result<G,GroupDecodingError> {
+        ZCashCodec::decode_compressed(bytes)
+    }
+
+    fn encode_uncompressed<G: CurveAffine>(point: &G) -> Vec<u8> {
+        ZCashCodec::encode_uncompressed(point)
+    }
+
+    fn decode_uncompressed<G: CurveAffine>(bytes: &[u8]) -> Result<G,GroupDecodingError> {
+        ZCashCodec::decode_uncompressed(bytes)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng};  // Rng
+
+    use pairing::CurveProjective;
+
+    use super::*;
+    use super::super::*;
+
+    fn round_trip<C: PointCodec>(signed: &single::SignedMessage<ZBLS>) {
+        let publickey = PublicKey::<ZBLS>::decode::<C>(&signed.publickey.encode::<C>()).unwrap();
+        let signature = Signature::<ZBLS>::decode::<C>(&signed.signature.encode::<C>()).unwrap();
+        assert!( publickey == signed.publickey && signature == signed.signature );
+
+        let point = signed.signature.0.into_affine();
+        let uncompressed = C::encode_uncompressed(&point);
+        assert!( uncompressed.len() == 192 );
+        assert!( C::decode_uncompressed::<<<ZBLS as EngineBLS>::SignatureGroup as CurveProjective>::Affine>(&uncompressed).unwrap() == point );
+
+        let encoded = signed.publickey.encode::<C>();
+        assert!( PublicKey::<ZBLS>::decode::<C>(&encoded[1..]).is_err() );
+    }
+
+    #[test]
+    fn codecs() {
+        let mut keypair = Keypair::<ZBLS>::generate(thread_rng());
+        let signed = keypair.sign(&Context::new(b"ctx"),b"test message");
+        round_trip::<ZCashCodec>(&signed);
+        round_trip::<Eth2Codec>(&signed);
+
+        assert!( signed.publickey.encode::<ZCashCodec>() == signed.publickey.to_bytes().to_vec() );
+        assert!( signed.signature.encode::<ZCashCodec>() == signed.signature.to_bytes().to_vec() );
+        assert!( signed.publickey.encode::<Eth2Codec>() == signed.publickey.encode::<ZCashCodec>() );
+        assert!( signed.signature.encode::<Eth2Codec>() == signed.signature.encode::<ZCashCodec>() );
+    }
+
+    #[test]
+    fn eth2_interop() {
+        // Public key from the EIP-2335 keystore test vectors.
+        let hex = "9612d7a727c9d0a22e185a1c768478dfe919cada9266988cb32359c11f2b7b27f4ae4040902382ae2910c15e2b420d07";
+        let bytes = (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i+2], 16).unwrap())
+            .collect::<Vec<u8>>();
+        let publickey = PublicKey::<ZBLS>::decode::<Eth2Codec>(&bytes).unwrap();
+        assert!( publickey.encode::<Eth2Codec>() == bytes );
+        assert!( PublicKey::<ZBLS>::decode::<ZCashCodec>(&bytes).unwrap() == publickey );
+
+        // Its big endian secret key derives the same public key.
+        let mut secret = [0u8; 32];
+        let secret_hex = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        for (i,b) in secret.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&secret_hex[2*i..2*i+2], 16).unwrap();
+        }
+        secret.reverse();
+        let secret = SecretKeyVT::<ZBLS>::from_bytes(&secret).unwrap();
+        assert!( secret.into_public().encode::<Eth2Codec>() == bytes );
+    }
+}
@@ -22,8 +22,71 @@ use std::collections::HashMap;
 
 use super::*;
 use super::single::SignedMessage;
+use super::distinct::{Contributions,UnknownContribution};
 use super::verifiers::verify_with_distinct_messages;
 
+
+/// Below this many points, `msm` uses independent scalar multiplications.
+const MSM_NAIVE_THRESHOLD: usize = 8;
+
+/// Extract `width` bits starting at bit `start` from little endian limbs.
+fn window_bits(limbs: &[u64], start: usize, width: usize) -> usize {
+    let mut bits = 0usize;
+    for j in 0..width {
+        let i = start + j;
+        if i / 64 < limbs.len() && (limbs[i / 64] >> (i % 64)) & 1 == 1 {
+            bits |= 1 << j;
+        }
+    }
+    bits
+}
+
+/// Multi-scalar multiplication `sum_i scalars[i] * points[i]`
+///
+/// We use Pippenger's bucket method with `c` bit windows, which costs
+/// roughly `b (n + 2^c) / c` additions for `n` points and `b` bit scalars,
+/// instead of the `b` doublings and additions per point required by
+/// independent scalar multiplications.  Below `MSM_NAIVE_THRESHOLD`
+/// points, the bucketing overhead dominates, so we fall back to
+/// independent scalar multiplications.
+///
+/// We run in variable time, so never pass secret scalars here.
+pub fn msm<G: CurveProjective>(points: &[G], scalars: &[G::Scalar]) -> G {
+    assert!(points.len() == scalars.len(), "Multi-scalar multiplication requires one scalar per point");
+    let n = points.len();
+    if n < MSM_NAIVE_THRESHOLD {
+        let mut acc = G::zero();
+        for (point,scalar) in points.iter().zip(scalars) {
+            let mut p = *point;
+            p.mul_assign(*scalar);
+            acc.add_assign(&p);
+        }
+        return acc;
+    }
+
+    let c = if n < 32 { 3 } else { (n as f64).ln().ceil() as usize + 2 };
+    let scalars = scalars.iter().map(|s| s.into_repr()).collect::<Vec<_>>();
+    let num_bits = <G::Scalar as PrimeField>::NUM_BITS as usize;
+    let mut acc = G::zero();
+    let mut window = num_bits.div_ceil(c);
+    while window > 0 {
+        window -= 1;
+        for _ in 0..c { acc.double(); }
+        let mut buckets = vec![G::zero(); (1 << c) - 1];
+        for (point,scalar) in points.iter().zip(scalars.iter()) {
+            let index = window_bits(scalar.as_ref(), window * c, c);
+            if index != 0 { buckets[index - 1].add_assign(point); }
+        }
+        // Adding running sums yields sum_j j * buckets[j-1]
+        let mut running = G::zero();
+        for bucket in buckets.iter().rev() {
+            running.add_assign(bucket);
+            acc.add_assign(&running);
+        }
+    }
+    acc
+}
+
 /// Delinearized batched and aggregated BLS signatures.
 ///
 /// We caution that this type only represents one of several
@@ -38,6 +101,8 @@ use super::verifiers::verify_with_distinct_messages;
 pub struct Delinearized<E: EngineBLS> {
     key: Shake128,
     messages_n_publickeys: HashMap<Message,PublicKey<E>>,
+    /// Undelinearized public keys contributed to each message
+    contributions: Contributions<E>,
     signature: Signature<E>,
 }
 
@@ -46,6 +111,7 @@ impl<E: EngineBLS> Clone for Delinearized<E> {
         Delinearized {
             key: self.key.clone(),
             messages_n_publickeys: self.messages_n_publickeys.clone(),
+            contributions: self.contributions.clone(),
             signature: self.signature.clone(),
         }
     }
@@ -74,6 +140,7 @@ impl<E: EngineBLS> Delinearized<E> {
         Delinearized {
             key,
             messages_n_publickeys: HashMap::new(),
+            contributions: Contributions::new(),
             signature: Signature(E::SignatureGroup::zero()),
         }
     }
@@ -126,6 +193,7 @@ impl<E: EngineBLS> Delinearized<E> {
     /// recommend instead using a custom types like `BitPoPSignedMessage`.
     pub fn add_message_n_publickey(&mut self, message: &Message, mut publickey: PublicKey<E>) -> E::Scalar {
         let mask = self.mask(&publickey);
+        self.contributions.add(message,&publickey);
         // We must use projective corrdinates here, dispite converting to
         // affine just above, because only `CurveProjective::mul_assign`
         // skips doubling until a set bit is found.
@@ -150,6 +218,56 @@ impl<E: EngineBLS> Delinearized<E> {
         self.add_delinearized_signature(&signature);
     }
 
+    /// Aggregage many BLS signatures from singletons using delinearization
+    ///
+    /// We compute the same aggregate as calling `add` repeatedly, but
+    /// do the delinearization with multi-scalar multiplications, one
+    /// for the signatures and one for the signers of each message.
+    pub fn add_batch(&mut self, signed: &[SignedMessage<E>]) {
+        let masks = signed.iter().map(|s| self.mask(&s.publickey)).collect::<Vec<_>>();
+        let signatures = signed.iter().map(|s| s.signature.0).collect::<Vec<_>>();
+        self.add_delinearized_signature(&Signature(msm(&signatures,&masks)));
+
+        let mut by_message = HashMap::new();
+        for (s,mask) in signed.iter().zip(masks) {
+            let entry = by_message.entry(s.message).or_insert_with(|| (Vec::new(),Vec::new()));
+            entry.0.push(s.publickey.0);
+            entry.1.push(mask);
+            self.contributions.add(&s.message,&s.publickey);
+        }
+        for (message,(publickeys,masks)) in by_message {
+            let publickey = PublicKey(msm(&publickeys,&masks));
+            self.messages_n_publickeys.entry(message)
+                .and_modify(|pk0| pk0.0.add_assign(&publickey.0) )
+                .or_insert(publickey);
+        }
+    }
+
+    /// Remove a BLS signature previously aggregated from a singleton
+    ///
+    /// We recompute the delinearization mask from our key and the
+    /// signer's public key, and subtract the delinearized signature
+    /// and public key.  We track which public keys contributed to each
+    /// message, so we return an error, leaving `self` unchanged, if
+    /// this signer never contributed this message.
+    pub fn remove(&mut self, signed: &SignedMessage<E>) -> Result<(),UnknownContribution> {
+        let last = self.contributions.remove(&signed.message,&signed.publickey) ?;
+        let mask = self.mask(&signed.publickey);
+        if last {
+            self.messages_n_publickeys.remove(&signed.message);
+        } else {
+            let mut publickey = signed.publickey;
+            publickey.0.mul_assign(mask);
+            self.messages_n_publickeys.get_mut(&signed.message)
+                .expect("Contributions track messages_n_publickeys")
+                .0.sub_assign(&publickey.0);
+        }
+        let mut signature = signed.signature;
+        signature.0.mul_assign(mask);
+        self.signature.0.sub_assign(&signature.0);
+        Ok(())
+    }
+
     /// Test that two `Delinearized` use the same key.
     ///
     /// You should call this before calling `merge`, although
@@ -175,6 +293,7 @@ impl<E: EngineBLS> Delinearized<E> {
                 .and_modify(|pk0| pk0.0.add_assign(&publickey.0) )
                 .or_insert(*publickey);
         }
+        self.contributions.merge(&other.contributions);
         self.signature.0.add_assign(&other.signature.0);
         // Ok(())
     }
@@ -199,13 +318,14 @@ mod tests {
 
     #[test]
     fn delinearized() {
-        let msg1 = Message::new(b"ctx",b"some message");
+        let context = Context::new(b"ctx");
+        let msg1 = b"some message";
 
         let k = |_| Keypair::<ZBLS>::generate(thread_rng());
         let mut keypairs = (0..4).into_iter().map(k).collect::<Vec<_>>();
         let dup = keypairs[3].clone();
         keypairs.push(dup);
-        let sigs1 = keypairs.iter_mut().map(|k| k.sign(msg1)).collect::<Vec<_>>();
+        let sigs1 = keypairs.iter_mut().map(|k| k.sign(&context,msg1)).collect::<Vec<_>>();
 
         let mut dl = Delinearized::<ZBLS>::new_batched();
         for sig in sigs1.iter() {
@@ -223,5 +343,89 @@ mod tests {
         assert!( dl.verify() );
         // TODO: more more
     }
+
+    fn naive_msm<G: CurveProjective>(points: &[G], scalars: &[G::Scalar]) -> G {
+        points.iter().zip(scalars).fold(G::zero(), |mut acc,(point,scalar)| {
+            let mut p = *point;
+            p.mul_assign(*scalar);
+            acc.add_assign(&p);
+            acc
+        })
+    }
+
+    fn msm_matches_naive<G: CurveProjective>() {
+        let mut rng = thread_rng();
+        for n in [0usize, 1, 2, 7, 8, 9, 31, 32, 33, 100].iter().cloned() {
+            let points = (0..n).map(|_| rng.gen::<G>()).collect::<Vec<_>>();
+            let scalars = (0..n).map(|_| rng.gen::<G::Scalar>()).collect::<Vec<_>>();
+            assert!( msm(&points,&scalars) == naive_msm(&points,&scalars) );
+        }
+        assert!( msm::<G>(&[],&[]).is_zero() );
+
+        // Small scalars leave most windows empty
+        let points = (0..20).map(|_| rng.gen::<G>()).collect::<Vec<_>>();
+        let scalars = (0..20u64).map(|i| <G::Scalar as PrimeField>::from_repr(i.into()).unwrap()).collect::<Vec<_>>();
+        assert!( msm(&points,&scalars) == naive_msm(&points,&scalars) );
+    }
+
+    #[test]
+    fn multi_scalar_multiplication() {
+        msm_matches_naive::<<ZBLS as EngineBLS>::PublicKeyGroup>();
+        msm_matches_naive::<<ZBLS as EngineBLS>::SignatureGroup>();
+    }
+
+    #[test]
+    fn delinearized_batch() {
+        let context = Context::new(b"ctx");
+        let msgs: [&[u8]; 2] = [ b"some message", b"another message" ];
+
+        let k = |_| Keypair::<ZBLS>::generate(thread_rng());
+        let mut keypairs = (0..12).into_iter().map(k).collect::<Vec<_>>();
+        let sigs = keypairs.iter_mut().enumerate()
+            .map(|(i,k)| k.sign(&context,msgs[i % 2]))
+            .collect::<Vec<_>>();
+
+        let mut dl = Delinearized::<ZBLS>::new_keyed(b"key");
+        for sig in sigs.iter() {
+            dl.add(sig);
+        }
+        let mut dl_batch = Delinearized::<ZBLS>::new_keyed(b"key");
+        dl_batch.add_batch(&sigs[..3]);
+        dl_batch.add_batch(&sigs[3..]);
+        assert!( dl_batch.signature == dl.signature );
+        assert!( dl_batch.messages_n_publickeys == dl.messages_n_publickeys );
+        assert!( dl_batch.verify() );
+    }
+
+    #[test]
+    fn delinearized_remove() {
+        let context = Context::new(b"ctx");
+        let msg1 = b"some message";
+        let msg2 = b"another message";
+
+        let k = |_| Keypair::<ZBLS>::generate(thread_rng());
+        let mut keypairs = (0..3).into_iter().map(k).collect::<Vec<_>>();
+        let sigs = vec![ keypairs[0].sign(&context,msg1), keypairs[1].sign(&context,msg1), keypairs[2].sign(&context,msg1) ];
+
+        let mut dl = Delinearized::<ZBLS>::new_keyed(b"key");
+        for sig in sigs.iter() { dl.add(sig); }
+        let mut expected = Delinearized::<ZBLS>::new_keyed(b"key");
+        expected.add(&sigs[0]);
+        expected.add(&sigs[2]);
+
+        assert!( dl.remove(&sigs[1]).is_ok() );
+        assert!( dl.signature == expected.signature );
+        assert!( dl.messages_n_publickeys == expected.messages_n_publickeys );
+        assert!( dl.verify() );
+        assert!( dl.remove(&keypairs[1].sign(&context,msg2)).is_err() );
+        assert!( dl.verify() );
+
+        // Other signers of msg1 remain, but this one no longer contributes.
+        let before = dl.clone();
+        assert!( dl.remove(&sigs[1]).is_err() );
+        assert!( dl.signature == before.signature );
+        assert!( dl.messages_n_publickeys == before.messages_n_publickeys );
+        assert!( dl.verify() );
+    }
 }
 
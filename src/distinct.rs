@@ -31,25 +31,145 @@ use super::single::SignedMessage;
 use super::verifiers::verify_with_distinct_messages;
 
 
-/// Error tyoe for non-distinct messages found during distinct
-/// message aggregation.
+/// Error type for distinct message aggregation.
 ///
 /// There are numerous scenarios that make recovery from such errors
 /// impossible.  We therefore destroy the aggregate signature struct
 /// whenever creating this, so that users cannot respond incorrectly
 /// to an error message.
 #[derive(Debug)]
-pub struct AttackViaDuplicateMessages;
+pub enum DistinctMessagesError {
+    /// Attempted to aggregate duplicate messages.
+    AttackViaDuplicateMessages,
+    /// Attempted to aggregate a contribution by the identity public key,
+    /// which verifies on any message without any secret key.
+    IdentityPublicKey,
+}
+
+impl ::std::fmt::Display for DistinctMessagesError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        use self::DistinctMessagesError::*;
+        match self {
+            AttackViaDuplicateMessages => write!(f, "Attempted to aggregate duplicate messages."),
+            IdentityPublicKey => write!(f, "Attempted to aggregate the identity public key."),
+        }
+    }
+}
+
+impl ::std::error::Error for DistinctMessagesError {
+    fn description(&self) -> &str {
+        use self::DistinctMessagesError::*;
+        match self {
+            AttackViaDuplicateMessages => "Attempted to aggregate duplicate messages.",
+            IdentityPublicKey => "Attempted to aggregate the identity public key.",
+        }
+    }
+}
+
+/// Error type for removing a contribution never included in an
+/// aggregate signature.
+///
+/// We leave the aggregate signature unchanged when returning this,
+/// so users may recover from such errors.
+#[derive(Debug)]
+pub struct UnknownContribution;
 
-impl ::std::fmt::Display for AttackViaDuplicateMessages {
+impl ::std::fmt::Display for UnknownContribution {
     fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-        write!(f, "Attempted to aggregate duplicate messages.")
+        write!(f, "Attempted to remove a contribution never aggregated.")
     }
 }
 
-impl ::std::error::Error for AttackViaDuplicateMessages {
+impl ::std::error::Error for UnknownContribution {
     fn description(&self) -> &str {
-        "Attempted to aggregate duplicate messages." 
+        "Attempted to remove a contribution never aggregated."
+    }
+}
+
+/// Error type for aggregating a contribution by the identity public key.
+///
+/// We leave the aggregate signature unchanged when returning this.
+#[derive(Debug)]
+pub struct IdentityPublicKey;
+
+impl ::std::fmt::Display for IdentityPublicKey {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        write!(f, "Attempted to aggregate the identity public key.")
+    }
+}
+
+impl ::std::error::Error for IdentityPublicKey {
+    fn description(&self) -> &str {
+        "Attempted to aggregate the identity public key."
+    }
+}
+
+/// Public keys contributed to each message of an aggregate signature,
+/// counted with multiplicity.
+///
+/// Aggregates that merge the public keys of all signers of the same
+/// message cannot tell which signers contributed, so they track
+/// contributions here, and `remove` only subtracts real contributions.
+pub(crate) struct Contributions<E: EngineBLS> {
+    /// Contributions by each signer to each message
+    by_signer: HashMap<(Message,PublicKey<E>),usize>,
+    /// Total contributions to each message
+    by_message: HashMap<Message,usize>,
+}
+
+impl<E: EngineBLS> Clone for Contributions<E> {
+    fn clone(&self) -> Contributions<E> {
+        Contributions {
+            by_signer: self.by_signer.clone(),
+            by_message: self.by_message.clone(),
+        }
+    }
+}
+
+impl<E: EngineBLS> Default for Contributions<E> {
+    fn default() -> Contributions<E> {
+        Contributions::new()
+    }
+}
+
+impl<E: EngineBLS> Contributions<E> {
+    pub(crate) fn new() -> Contributions<E> {
+        Contributions { by_signer: HashMap::new(), by_message: HashMap::new() }
+    }
+
+    /// Record one contribution by `publickey` to `message`.
+    pub(crate) fn add(&mut self, message: &Message, publickey: &PublicKey<E>) {
+        self.add_many(message,publickey,1);
+    }
+
+    fn add_many(&mut self, message: &Message, publickey: &PublicKey<E>, count: usize) {
+        *self.by_signer.entry((*message,*publickey)).or_insert(0) += count;
+        *self.by_message.entry(*message).or_insert(0) += count;
+    }
+
+    /// Forget one contribution by `publickey` to `message`, returning
+    /// whether `message` has no remaining contributors.
+    ///
+    /// We return an error, leaving `self` unchanged, if `publickey`
+    /// never contributed to `message`.
+    pub(crate) fn remove(&mut self, message: &Message, publickey: &PublicKey<E>) -> Result<bool,UnknownContribution> {
+        let key = (*message,*publickey);
+        let count = self.by_signer.get_mut(&key).ok_or(UnknownContribution) ?;
+        *count -= 1;
+        if *count == 0 { self.by_signer.remove(&key); }
+        // Any contribution by `publickey` was also counted for `message`.
+        let total = self.by_message.get_mut(message).expect("Contributions counts agree, qed");
+        *total -= 1;
+        if *total > 0 { return Ok(false); }
+        self.by_message.remove(message);
+        Ok(true)
+    }
+
+    /// Record every contribution from `other`.
+    pub(crate) fn merge(&mut self, other: &Contributions<E>) {
+        for ((message,publickey),count) in other.by_signer.iter() {
+            self.add_many(message,publickey,*count);
+        }
     }
 }
 
@@ -74,12 +194,21 @@ impl ::std::error::Error for AttackViaDuplicateMessages {
 /// we do yet not provide any serialization scheme for the aggregate
 /// version.  Instead, you should serialize the aggregated signature
 /// seperately, and reconstruct this type using its `add_*` methods.
-#[derive(Clone)]
+// #[derive(Clone)]
 pub struct DistinctMessages<E: EngineBLS> {
     messages_n_publickeys: HashMap<Message,PublicKey<E>>,
     signature: Signature<E>,
 }
 
+impl<E: EngineBLS> Clone for DistinctMessages<E> {
+    fn clone(&self) -> DistinctMessages<E> {
+        DistinctMessages {
+            messages_n_publickeys: self.messages_n_publickeys.clone(),
+            signature: self.signature,
+        }
+    }
+}
+
 impl<'a,E: EngineBLS> Signed for &'a DistinctMessages<E> {
     type E = E;
 
@@ -131,10 +260,13 @@ impl<E: EngineBLS> DistinctMessages<E> {
     pub fn add_message_n_publickey(mut self, message: Message, publickey: PublicKey<E>)
      -> DistinctMessagesResult<E>
      {
+        if publickey.0.is_zero() {
+            return Err(DistinctMessagesError::IdentityPublicKey);
+        }
         if let Some(_old_publickey) = self.messages_n_publickeys.insert(message,publickey) {
             // We need not recover from this error because the hash map gets erased.
             // self.messages_n_publickeys.insert(signed.message,old_publickey);
-            return Err(AttackViaDuplicateMessages);
+            return Err(DistinctMessagesError::AttackViaDuplicateMessages);
         }
         Ok(self)
     }
@@ -160,21 +292,101 @@ impl<E: EngineBLS> DistinctMessages<E> {
         // duplicates cause our hashmap to be freed anyways.
         // for (m,_pk) in signed.messages_n_publickeys.iter() {
         //     if self.messages_n_publickeys.contains_key(m) {
-        //      return Err(AttackViaDuplicateMessages);
+        //      return Err(DistinctMessagesError::AttackViaDuplicateMessages);
         //     }
         // }
         for (m,pk) in signed.messages_n_publickeys.iter() {
             // assert!(self.messages_n_publickeys.insert(*m,*pk).is_none());
             if self.messages_n_publickeys.insert(*m,*pk).is_some() {
-                return Err(AttackViaDuplicateMessages);
+                return Err(DistinctMessagesError::AttackViaDuplicateMessages);
             }
         }
         self.add_signature(&signed.signature);
         Ok(self)
     }
+
+    /// Remove a BLS signature previously aggregated from a singleton
+    ///
+    /// We subtract the signature and forget its message, so the result
+    /// equals aggregating only the remaining singletons.  We return
+    /// an error, leaving `self` unchanged, if this message was not
+    /// signed by this public key.  We cannot detect if the signature
+    /// differs from the one previously added, but then verification fails.
+    pub fn remove(&mut self, signed: &SignedMessage<E>) -> Result<(),UnknownContribution>
+    {
+        if self.messages_n_publickeys.get(&signed.message) != Some(&signed.publickey) {
+            return Err(UnknownContribution);
+        }
+        self.messages_n_publickeys.remove(&signed.message);
+        self.signature.0.sub_assign(&signed.signature.0);
+        Ok(())
+    }
 }
 
-pub type DistinctMessagesResult<E> = Result<DistinctMessages<E>,AttackViaDuplicateMessages>;
+pub type DistinctMessagesResult<E> = Result<DistinctMessages<E>,DistinctMessagesError>;
+
+/// Incremental accumulator for aggregate BLS signatures on distinct messages
+///
+/// `DistinctMessages` consumes itself upon encountering duplicate
+/// messages, which suits verifiers, but third parties who combine
+/// signatures from gossip need to continue after refusing some bad
+/// contribution.  We therefore aggregate by mutable reference, and
+/// leave ourselves unchanged whenever we return an error.
+///
+/// We accept any `Signed` type, including other aggregates, and
+/// `finalize` produces a `DistinctMessages` for verification.
+// #[derive(Clone)]
+pub struct AggregateSignature<E: EngineBLS>(DistinctMessages<E>);
+
+impl<E: EngineBLS> Clone for AggregateSignature<E> {
+    fn clone(&self) -> AggregateSignature<E> {
+        AggregateSignature(self.0.clone())
+    }
+}
+
+impl<E: EngineBLS> Default for AggregateSignature<E> {
+    fn default() -> AggregateSignature<E> {
+        AggregateSignature::new()
+    }
+}
+
+impl<E: EngineBLS> AggregateSignature<E> {
+    pub fn new() -> AggregateSignature<E> {
+        AggregateSignature(DistinctMessages::new())
+    }
+
+    /// Aggregate all messages and public keys from `signed`, along
+    /// with its signature.
+    ///
+    /// We refuse `signed` entirely if any of its messages were already
+    /// aggregated, if it repeats some message itself, or if it contains
+    /// the identity public key, in which case we remain unchanged.
+    pub fn aggregate<S: Signed<E=E>>(&mut self, signed: S) -> Result<(),DistinctMessagesError>
+    {
+        let signature = signed.signature();
+        let mut messages = HashMap::new();
+        for (m,pk) in signed.messages_and_publickeys() {
+            let m: Message = *m.borrow();
+            let pk: PublicKey<E> = *pk.borrow();
+            if pk.0.is_zero() {
+                return Err(DistinctMessagesError::IdentityPublicKey);
+            }
+            if self.0.messages_n_publickeys.contains_key(&m)
+                || messages.insert(m,pk).is_some()
+            {
+                return Err(DistinctMessagesError::AttackViaDuplicateMessages);
+            }
+        }
+        self.0.messages_n_publickeys.extend(messages);
+        self.0.add_signature(&signature);
+        Ok(())
+    }
+
+    /// Produce our aggregate signature for verification.
+    pub fn finalize(self) -> DistinctMessages<E> {
+        self.0
+    }
+}
 
 /*
 TODO: Adopt .collect::<DistinctMessagesResult<E>>() via FromIterator
@@ -197,18 +409,19 @@ mod tests {
 
     #[test]
     fn distinct_messages() {
-        let msgs = [ Message::new(b"ctx",b"Message1"), Message::new(b"ctx",b"Message1"), Message::new(b"ctx",b"Message2"), Message::new(b"ctx",b"Message3"), Message::new(b"ctx",b"Message4") ];
+        let context = Context::new(b"ctx");
+        let msgs: [&[u8]; 5] = [ b"Message1", b"Message1", b"Message2", b"Message3", b"Message4" ];
 
         let k = |_| Keypair::<ZBLS>::generate(thread_rng());
         let mut keypairs = (0..4).into_iter().map(k).collect::<Vec<_>>();
         let dup = keypairs[3].clone();
         keypairs.push(dup);
 
-        let sigs = msgs.iter().zip(keypairs.iter_mut()).map(|(m,k)| k.sign(*m)).collect::<Vec<_>>();  
+        let sigs = msgs.iter().zip(keypairs.iter_mut()).map(|(m,k)| k.sign(&context,m)).collect::<Vec<_>>();  
 
         let dm_new = || DistinctMessages::<ZBLS>::new();
         fn dm_add(dm: DistinctMessages<ZBLS>, sig: &SignedMessage<ZBLS>)
-         -> Result<DistinctMessages<ZBLS>,AttackViaDuplicateMessages>
+         -> Result<DistinctMessages<ZBLS>,DistinctMessagesError>
             { dm.add(sig) }
 
         let mut dms = sigs.iter().skip(1).try_fold(dm_new(), dm_add).unwrap();
@@ -226,7 +439,102 @@ mod tests {
         let dms2 = sigs.iter().skip(3).try_fold(dm_new(), dm_add).unwrap();
         assert!( dms1.merge(&dms2).unwrap().signature == dms.signature );
 
-        *(dms.messages_n_publickeys.get_mut(&msgs[1]).unwrap()) = keypairs[0].public.clone();
+        *(dms.messages_n_publickeys.get_mut(&sigs[1].message).unwrap()) = keypairs[0].public.clone();
         assert!( ! dms.verify() , "Verification by an incorrect signer passed");
     }
+
+    #[test]
+    fn remove_signer() {
+        let context = Context::new(b"ctx");
+        let msgs: [&[u8]; 3] = [ b"Message1", b"Message2", b"Message3" ];
+        let mut keypairs = (0..3).map(|_| Keypair::<ZBLS>::generate(thread_rng())).collect::<Vec<_>>();
+        let sigs = msgs.iter().zip(keypairs.iter_mut()).map(|(m,k)| k.sign(&context,m)).collect::<Vec<_>>();
+
+        let mut dms = sigs.iter().try_fold(DistinctMessages::<ZBLS>::new(), |dm,s| dm.add(s)).unwrap();
+        assert!( dms.remove(&sigs[1]).is_ok() );
+        let expected = DistinctMessages::<ZBLS>::new().add(&sigs[0]).unwrap().add(&sigs[2]).unwrap();
+        assert!( dms.signature == expected.signature );
+        assert!( dms.messages_n_publickeys == expected.messages_n_publickeys );
+        assert!( dms.verify() );
+
+        assert!( dms.remove(&sigs[1]).is_err() );
+        let mut wrong_signer = sigs[0].clone();
+        wrong_signer.publickey = sigs[2].publickey;
+        assert!( dms.remove(&wrong_signer).is_err() );
+        assert!( dms.signature == expected.signature );
+        assert!( dms.verify() );
+    }
+
+    #[test]
+    fn aggregate_incrementally() {
+        let context = Context::new(b"ctx");
+        let mut keypairs = (0..5).map(|_| Keypair::<ZBLS>::generate(thread_rng())).collect::<Vec<_>>();
+        let sigs = keypairs.iter_mut().enumerate().map(|(i,k)| k.sign(&context,&[i as u8])).collect::<Vec<_>>();
+        let all_at_once = sigs.iter().try_fold(DistinctMessages::<ZBLS>::new(), |dm,s| dm.add(s)).unwrap();
+
+        let mut agg = AggregateSignature::<ZBLS>::new();
+        for s in sigs.iter() {
+            assert!( agg.aggregate(s).is_ok() );
+        }
+        let before = agg.clone().finalize();
+
+        // Clones aggregate independently, even though `ZBLS` is not `Clone`.
+        let mut fork = agg.clone();
+        assert!( fork.aggregate(&keypairs[0].sign(&context,b"fork")).is_ok() );
+        assert!( fork.finalize().messages_n_publickeys.len() == before.messages_n_publickeys.len() + 1 );
+
+        // Refuse duplicates, both singletons and within aggregates.
+        let mut duplicate = keypairs[0].sign(&context,&[3u8]);
+        assert!( agg.aggregate(&duplicate).is_err() );
+        duplicate.message = Message::new(&context,b"fresh");
+        let mixed = DistinctMessages::<ZBLS>::new().add(&duplicate).unwrap().add(&sigs[2]).unwrap();
+        assert!( agg.aggregate(&mixed).is_err() );
+        let dm = agg.finalize();
+        assert!( dm.signature == before.signature );
+        assert!( dm.messages_n_publickeys == before.messages_n_publickeys );
+
+        assert!( dm.signature == all_at_once.signature );
+        assert!( dm.messages_n_publickeys == all_at_once.messages_n_publickeys );
+        assert!( dm.verify() );
+
+        // Fold aggregates too
+        let mut agg = AggregateSignature::<ZBLS>::new();
+        let first = sigs.iter().take(2).try_fold(DistinctMessages::<ZBLS>::new(), |dm,s| dm.add(s)).unwrap();
+        assert!( agg.aggregate(&first).is_ok() );
+        for s in sigs.iter().skip(2) {
+            assert!( agg.aggregate(s).is_ok() );
+        }
+        let dm = agg.finalize();
+        assert!( dm.signature == all_at_once.signature );
+        assert!( dm.verify() );
+    }
+
+    #[test]
+    fn identity_publickey() {
+        // The identity public key verifies the identity signature on any message.
+        let forged = SignedMessage::<ZBLS> {
+            message: Message::new(&Context::new(b"ctx"),b"never signed"),
+            publickey: PublicKey(<ZBLS as EngineBLS>::PublicKeyGroup::zero()),
+            signature: Signature(<ZBLS as EngineBLS>::SignatureGroup::zero()),
+        };
+        assert!( forged.verify() );
+
+        match DistinctMessages::<ZBLS>::new().add(&forged) {
+            Err(DistinctMessagesError::IdentityPublicKey) => {},
+            _ => panic!("Identity public key was aggregated"),
+        }
+        let signed = Keypair::<ZBLS>::generate(thread_rng()).sign(&Context::new(b"ctx"),b"signed");
+        let mut agg = AggregateSignature::<ZBLS>::new();
+        assert!( agg.aggregate(&signed).is_ok() );
+        match agg.aggregate(&forged) {
+            Err(DistinctMessagesError::IdentityPublicKey) => {},
+            _ => panic!("Identity public key was aggregated"),
+        }
+        match agg.aggregate(&signed) {
+            Err(DistinctMessagesError::AttackViaDuplicateMessages) => {},
+            _ => panic!("Duplicate message was aggregated"),
+        }
+        let dm = agg.finalize();
+        assert!( dm.messages_and_publickeys().len() == 1 && dm.signature == signed.signature );
+    }
 }
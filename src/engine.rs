@@ -21,7 +21,7 @@ use std::borrow::Borrow;
 
 use ff::{Field, PrimeField, ScalarEngine, SqrtField}; // PrimeFieldDecodingError, PrimeFieldRepr
 use pairing::{CurveAffine, CurveProjective, Engine};
-use rand::{Rand, Rng};
+use rand::{Rand, Rng, thread_rng};
 
 
 /// A weakening of `pairing::Engine` to permit transposing the groups.
@@ -60,9 +60,14 @@ pub trait EngineBLS {
         CurveProjective<Engine = Self::Engine, Scalar = Self::Scalar>
         + Into<<Self::SignatureGroup as CurveProjective>::Affine>;
 
-    /// Generate a random scalar for use as a secret key.
+    /// Generate a random nonzero scalar for use as a secret key.
+    ///
+    /// We resample zero, whose public key would be the identity.
     fn generate<R: Rng>(rng: &mut R) -> Self::Scalar {
-        Self::Scalar::rand(rng)
+        loop {
+            let scalar = Self::Scalar::rand(rng);
+            if ! scalar.is_zero() { return scalar; }
+        }
     }
 
     /// Hash one message to the signature curve.
@@ -123,6 +128,69 @@ pub trait EngineBLS {
                 .chain(::std::iter::once( (& g1_minus_generator.prepare(), signature) ))
         ) ).unwrap() == <Self::Engine as Engine>::Fqk::one()
     }
+
+    /// Run the Miller loop over the message and public key pairs of
+    /// some aggregate signature, but omit both the signature's own
+    /// `-g1` term and the final exponentiation.
+    ///
+    /// Pass accumulators from several aggregates to `verify_accumulated`
+    /// to share the final exponentiation, our most expensive step.
+    fn miller_accumulate<'a,I>(inputs: I) -> <Self::Engine as Engine>::Fqk
+    where
+        I: IntoIterator<Item = (
+            &'a <<Self::PublicKeyGroup as CurveProjective>::Affine as CurveAffine>::Prepared,
+            &'a <<Self::SignatureGroup as CurveProjective>::Affine as CurveAffine>::Prepared,
+        )>
+    {
+        Self::miller_loop(inputs)
+    }
+
+    /// Verify several aggregate signatures at once, given accumulators
+    /// from `miller_accumulate` along with their signatures, using
+    /// `thread_rng` for our random coefficients.
+    ///
+    /// See `verify_accumulated_with_rng`.
+    fn verify_accumulated(
+        accumulators: &[<Self::Engine as Engine>::Fqk],
+        signatures: &[Self::SignatureGroup],
+    ) -> bool
+    {
+        Self::verify_accumulated_with_rng(accumulators, signatures, thread_rng())
+    }
+
+    /// Verify several aggregate signatures at once, given accumulators
+    /// from `miller_accumulate` along with their signatures.
+    ///
+    /// We raise the `i`th accumulator to a random scalar `r_i` from the
+    /// caller supplied `rng`, and multiply the `i`th signature by `r_i`,
+    /// so that an adversary cannot move signature contributions between
+    /// aggregates, and we accept only if every aggregate is valid.
+    /// All `-g1` terms then cost one Miller loop step, before one final
+    /// exponentiation.  We reject if the slices differ in length.
+    /// Callers should supply a CSPRNG outside of tests.
+    fn verify_accumulated_with_rng<R: Rng>(
+        accumulators: &[<Self::Engine as Engine>::Fqk],
+        signatures: &[Self::SignatureGroup],
+        mut rng: R,
+    ) -> bool
+    {
+        if accumulators.len() != signatures.len() { return false; }
+        let mut signature = Self::SignatureGroup::zero();
+        let mut product = <Self::Engine as Engine>::Fqk::one();
+        for (acc,s) in accumulators.iter().zip(signatures) {
+            let r = Self::generate(&mut rng);
+            let mut sigma = *s;
+            sigma.mul_assign(r);
+            signature.add_assign(&sigma);
+            product.mul_assign(&acc.pow(r.into_repr()));
+        }
+        let mut g1_minus_generator = <Self::PublicKeyGroup as CurveProjective>::Affine::one();
+        g1_minus_generator.negate();
+        product.mul_assign(&Self::miller_loop(::std::iter::once(
+            (& g1_minus_generator.prepare(), & signature.into_affine().prepare())
+        )));
+        Self::final_exponentiation(&product).unwrap() == <Self::Engine as Engine>::Fqk::one()
+    }
 }
 
 
@@ -0,0 +1,498 @@
+//! ## IETF hash-to-curve for BLS12-381
+//!
+//! We implement RFC 9380's `hash_to_curve` for the
+//! `BLS12381G2_XMD:SHA-256_SSWU_RO_` suite, so that the `Ietf` engine
+//! hashes messages to the same points on G2 as other standard BLS
+//! implementations, like those used by Ethereum 2.0.
+//!
+//! We follow the RFC's straightforward, not constant time, simplified
+//! SWU algorithm, because we only ever hash public messages.  We map
+//! onto the 3-isogenous curve, apply the isogeny, and clear cofactors
+//! by multiplying with `h_eff`, exactly as RFC 9380 section 8.8.2
+//! specifies.
+//!
+//! The domain separation tag is a parameter, via `DomainSeparationTag`,
+//! so that consensus clients can match their own tag.
+//!
+//! Our usual signing methods hash `Message`, our own 32 byte digest of
+//! the `Context` and message, so those signatures verify only here.
+//! We therefore also provide `sign_ietf` and `verify_ietf` methods for
+//! `Ietf` keys, which hash the raw message bytes with `hash_to_g2`, and
+//! so interoperate with other standard BLS implementations.
+
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use ff::{Field, PrimeField, PrimeFieldRepr, SqrtField};
+use pairing::{CurveAffine, CurveProjective, EncodedPoint, Engine};
+use pairing::bls12_381::{Bls12, Fq, FqRepr, Fq2, Fr, G1, G2, G2Uncompressed};
+use sha2::{Digest, Sha256};
+
+use rand::Rng;
+
+use super::engine::{EngineBLS, UsualBLS, UnmutatedKeys, DeserializePublicKey};
+use super::single::{SecretKey, SecretKeyVT, PublicKey, Signature};
+
+
+/// Domain separation tag supplied to `hash_to_g2` by the `Ietf` engine.
+pub trait DomainSeparationTag {
+    const DST: &'static [u8];
+}
+
+/// Ciphersuite tag for the IETF BLS signature draft's basic scheme.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct BasicDST;
+
+impl DomainSeparationTag for BasicDST {
+    const DST: &'static [u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+}
+
+/// Ciphersuite tag for the IETF BLS signature draft's proof-of-possession
+/// scheme, which Ethereum 2.0 adopted.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct PopDST;
+
+impl DomainSeparationTag for PopDST {
+    const DST: &'static [u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+}
+
+
+/// Usual BLS orientation on BLS12-381, but hashing to the signature
+/// curve following RFC 9380 with domain separation tag `D::DST`.
+///
+/// Wrap this in `PoP` for proof-of-possession, as usual.
+pub struct Ietf<D: DomainSeparationTag>(PhantomData<D>);
+
+impl<D: DomainSeparationTag> Default for Ietf<D> {
+    fn default() -> Ietf<D> { Ietf(PhantomData) }
+}
+
+impl<D: DomainSeparationTag> EngineBLS for Ietf<D> {
+    type Engine = Bls12;
+    type Scalar = Fr;
+    type PublicKeyGroup = G1;
+    type SignatureGroup = G2;
+
+    fn hash_to_signature_curve<M: Borrow<[u8]>>(message: M) -> G2 {
+        hash_to_g2(message.borrow(), D::DST)
+    }
+
+    fn miller_loop<'a,I>(i: I) -> <Bls12 as Engine>::Fqk
+    where
+        I: IntoIterator<Item = (
+            &'a <<G1 as CurveProjective>::Affine as CurveAffine>::Prepared,
+            &'a <<G2 as CurveProjective>::Affine as CurveAffine>::Prepared,
+        )>,
+    {
+        UsualBLS::<Bls12>::miller_loop(i)
+    }
+
+    fn pairing<P,Q>(p: P, q: Q) -> <Bls12 as Engine>::Fqk
+    where
+        P: Into<<G1 as CurveProjective>::Affine>,
+        Q: Into<<G2 as CurveProjective>::Affine>,
+    {
+        Bls12::pairing(p,q)
+    }
+}
+
+impl<D: DomainSeparationTag> UnmutatedKeys for Ietf<D> {}
+impl<D: DomainSeparationTag> DeserializePublicKey for Ietf<D> {}
+
+impl<D: DomainSeparationTag> SecretKeyVT<Ietf<D>> {
+    /// Sign raw `message` bytes like standard BLS implementations do,
+    /// hashing them with `hash_to_g2` under `D::DST`, without side
+    /// channel protections from key mutation.
+    pub fn sign_ietf(&self, message: &[u8]) -> Signature<Ietf<D>> {
+        self.sign_point(hash_to_g2(message, D::DST))
+    }
+}
+
+impl<D: DomainSeparationTag> SecretKey<Ietf<D>> {
+    /// Sign raw `message` bytes like standard BLS implementations do,
+    /// after respliting the secret key for side channel protections.
+    pub fn sign_ietf<R: Rng>(&mut self, message: &[u8], rng: R) -> Signature<Ietf<D>> {
+        self.sign_point(hash_to_g2(message, D::DST), rng)
+    }
+}
+
+impl<D: DomainSeparationTag> PublicKey<Ietf<D>> {
+    /// Verify a signature on raw `message` bytes produced by `sign_ietf`,
+    /// or by any standard BLS implementation using `D::DST`.
+    pub fn verify_ietf(&self, message: &[u8], signature: &Signature<Ietf<D>>) -> bool {
+        signature.verify_point(hash_to_g2(message, D::DST), self)
+    }
+}
+
+
+/// RFC 9380 `expand_message_xmd` using SHA-256.
+///
+/// We hash domain separation tags longer than 255 bytes, as the RFC
+/// requires, and panic if `len_in_bytes` exceeds 8160 bytes.
+pub fn expand_message_xmd(msg: &[u8], dst: &[u8], len_in_bytes: usize) -> Vec<u8> {
+    const B_IN_BYTES: usize = 32;
+    const S_IN_BYTES: usize = 64;
+
+    let oversize;
+    let dst = if dst.len() > 255 {
+        oversize = Sha256::new().chain(b"H2C-OVERSIZE-DST-").chain(dst).result();
+        &oversize[..]
+    } else { dst };
+    let dst_prime = |h: Sha256| h.chain(dst).chain([dst.len() as u8]);
+
+    let ell = len_in_bytes.div_ceil(B_IN_BYTES);
+    assert!(ell <= 255, "expand_message_xmd output too long");
+
+    let b_0 = dst_prime(
+        Sha256::new()
+        .chain([0u8; S_IN_BYTES])
+        .chain(msg)
+        .chain((len_in_bytes as u16).to_be_bytes())
+        .chain([0u8])
+    ).result();
+    let mut b_i = dst_prime(Sha256::new().chain(b_0).chain([1u8])).result();
+
+    let mut uniform_bytes = Vec::with_capacity(ell * B_IN_BYTES);
+    uniform_bytes.extend_from_slice(&b_i);
+    for i in 2..=ell {
+        let mut xored = [0u8; B_IN_BYTES];
+        for (x,(b0,bi)) in xored.iter_mut().zip(b_0.iter().zip(b_i.iter())) {
+            *x = b0 ^ bi;
+        }
+        b_i = dst_prime(Sha256::new().chain(xored).chain([i as u8])).result();
+        uniform_bytes.extend_from_slice(&b_i);
+    }
+    uniform_bytes.truncate(len_in_bytes);
+    uniform_bytes
+}
+
+/// RFC 9380 `hash_to_curve` onto G2 of BLS12-381, following the
+/// `BLS12381G2_XMD:SHA-256_SSWU_RO_` suite with tag `dst`.
+pub fn hash_to_g2(msg: &[u8], dst: &[u8]) -> G2 {
+    static CONSTANTS: OnceLock<Constants> = OnceLock::new();
+    let constants = CONSTANTS.get_or_init(Constants::new);
+    let u = hash_to_field(msg, dst);
+    let mut q = map_to_curve(constants, &u[0]);
+    q.add_assign(&map_to_curve(constants, &u[1]));
+    clear_cofactor(&q)
+}
+
+
+/// Parse a field element from big endian hex, like RFC 9380 writes them.
+fn fq(hex: &str) -> Fq {
+    let mut repr = FqRepr::default();
+    for digit in hex.chars() {
+        repr.shl(4);
+        repr.add_nocarry(&FqRepr::from(digit.to_digit(16).unwrap() as u64));
+    }
+    Fq::from_repr(repr).unwrap()
+}
+
+fn fq2(c0: &str, c1: &str) -> Fq2 {
+    Fq2 { c0: fq(c0), c1: fq(c1) }
+}
+
+/// Curve and isogeny constants from RFC 9380 sections 8.8.2 and E.3.
+///
+/// Isogeny coefficients run from the constant term upwards.
+struct Constants {
+    /// `A'` for the isogenous curve `E'`
+    a: Fq2,
+    /// `B'` for the isogenous curve `E'`
+    b: Fq2,
+    /// Non-square `Z` used by simplified SWU
+    z: Fq2,
+    x_num: [Fq2; 4],
+    x_den: [Fq2; 3],
+    y_num: [Fq2; 4],
+    y_den: [Fq2; 4],
+}
+
+impl Constants {
+    fn new() -> Constants {
+        let mut z = fq2("2","1");
+        z.negate();
+        Constants {
+            a: fq2("0","f0"),
+            b: fq2("3f4","3f4"),
+            z,
+            x_num: [
+                fq2("5c759507e8e333ebb5b7a9a47d7ed8532c52d39fd3a042a88b58423c50ae15d5c2638e343d9c71c6238aaaaaaaa97d6",
+                    "5c759507e8e333ebb5b7a9a47d7ed8532c52d39fd3a042a88b58423c50ae15d5c2638e343d9c71c6238aaaaaaaa97d6"),
+                fq2("0",
+                    "11560bf17baa99bc32126fced787c88f984f87adf7ae0c7f9a208c6b4f20a4181472aaa9cb8d555526a9ffffffffc71a"),
+                fq2("11560bf17baa99bc32126fced787c88f984f87adf7ae0c7f9a208c6b4f20a4181472aaa9cb8d555526a9ffffffffc71e",
+                    "8ab05f8bdd54cde190937e76bc3e447cc27c3d6fbd7063fcd104635a790520c0a395554e5c6aaaa9354ffffffffe38d"),
+                fq2("171d6541fa38ccfaed6dea691f5fb614cb14b4e7f4e810aa22d6108f142b85757098e38d0f671c7188e2aaaaaaaa5ed1",
+                    "0"),
+            ],
+            x_den: [
+                fq2("0",
+                    "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaa63"),
+                fq2("c",
+                    "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaa9f"),
+                fq2("1","0"),
+            ],
+            y_num: [
+                fq2("1530477c7ab4113b59a4c18b076d11930f7da5d4a07f649bf54439d87d27e500fc8c25ebf8c92f6812cfc71c71c6d706",
+                    "1530477c7ab4113b59a4c18b076d11930f7da5d4a07f649bf54439d87d27e500fc8c25ebf8c92f6812cfc71c71c6d706"),
+                fq2("0",
+                    "5c759507e8e333ebb5b7a9a47d7ed8532c52d39fd3a042a88b58423c50ae15d5c2638e343d9c71c6238aaaaaaaa97be"),
+                fq2("11560bf17baa99bc32126fced787c88f984f87adf7ae0c7f9a208c6b4f20a4181472aaa9cb8d555526a9ffffffffc71c",
+                    "8ab05f8bdd54cde190937e76bc3e447cc27c3d6fbd7063fcd104635a790520c0a395554e5c6aaaa9354ffffffffe38f"),
+                fq2("124c9ad43b6cf79bfbf7043de3811ad0761b0f37a1e26286b0e977c69aa274524e79097a56dc4bd9e1b371c71c718b10",
+                    "0"),
+            ],
+            y_den: [
+                fq2("1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffa8fb",
+                    "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffa8fb"),
+                fq2("0",
+                    "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffa9d3"),
+                fq2("12",
+                    "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaa99"),
+                fq2("1","0"),
+            ],
+        }
+    }
+}
+
+/// Effective cofactor `h_eff` for G2 from RFC 9380 section 8.8.2.
+const H_EFF: &str = "bc69f08f2ee75b3584c6a0ea91b352888e2a8e9145ad7689986ff031508ffe1329c2f178731db956d82bf015d1212b02ec0ec69d7477c1ae954cbc06689f6a359894c0adebbf6b4e8020005aaa95551";
+
+/// Reduce 64 big endian bytes modulo `q`, via `hi * 2^256 + lo`.
+fn fq_from_okm(okm: &[u8]) -> Fq {
+    let half = |bytes: &[u8]| {
+        let mut padded = [0u8; 48];
+        padded[16..].copy_from_slice(bytes);
+        let mut repr = FqRepr::default();
+        repr.read_be(&padded[..]).unwrap();
+        Fq::from_repr(repr).unwrap()
+    };
+    let mut shift = FqRepr::from(1);
+    shift.shl(256);
+    let mut x = half(&okm[..32]);
+    x.mul_assign(&Fq::from_repr(shift).unwrap());
+    x.add_assign(&half(&okm[32..]));
+    x
+}
+
+/// RFC 9380 `hash_to_field` with `count = 2`, `m = 2`, and `L = 64`.
+fn hash_to_field(msg: &[u8], dst: &[u8]) -> [Fq2; 2] {
+    let uniform_bytes = expand_message_xmd(msg, dst, 256);
+    let mut u = [Fq2::zero(); 2];
+    for (ui,bytes) in u.iter_mut().zip(uniform_bytes.chunks(128)) {
+        ui.c0 = fq_from_okm(&bytes[..64]);
+        ui.c1 = fq_from_okm(&bytes[64..]);
+    }
+    u
+}
+
+/// RFC 9380 `sgn0` for `Fq2`
+fn sgn0(x: &Fq2) -> bool {
+    let sign_0 = x.c0.into_repr().is_odd();
+    let zero_0 = x.c0.is_zero();
+    let sign_1 = x.c1.into_repr().is_odd();
+    sign_0 || (zero_0 && sign_1)
+}
+
+/// `x^3 + A x + B`
+fn curve_rhs(c: &Constants, x: &Fq2) -> Fq2 {
+    let mut rhs = *x;
+    rhs.square();
+    rhs.add_assign(&c.a);
+    rhs.mul_assign(x);
+    rhs.add_assign(&c.b);
+    rhs
+}
+
+/// RFC 9380 `map_to_curve_simple_swu` onto `E'`, in affine coordinates.
+fn map_to_curve_simple_swu(c: &Constants, u: &Fq2) -> (Fq2,Fq2) {
+    let mut z_u2 = *u;
+    z_u2.square();
+    z_u2.mul_assign(&c.z);
+    let mut tv1 = z_u2;
+    tv1.square();
+    tv1.add_assign(&z_u2);
+
+    let x1 = match tv1.inverse() {
+        Some(mut x1) => {
+            // x1 = (-B / A) * (1 + tv1)
+            x1.add_assign(&Fq2::one());
+            x1.mul_assign(&c.b);
+            x1.mul_assign(&c.a.inverse().unwrap());
+            x1.negate();
+            x1
+        },
+        None => {
+            // x1 = B / (Z * A)
+            let mut za = c.z;
+            za.mul_assign(&c.a);
+            let mut x1 = c.b;
+            x1.mul_assign(&za.inverse().unwrap());
+            x1
+        },
+    };
+
+    let (x,mut y) = match curve_rhs(c,&x1).sqrt() {
+        Some(y1) => (x1,y1),
+        None => {
+            let mut x2 = z_u2;
+            x2.mul_assign(&x1);
+            let y2 = curve_rhs(c,&x2).sqrt()
+                .expect("g(x2) is square whenever g(x1) is not");
+            (x2,y2)
+        },
+    };
+    if sgn0(u) != sgn0(&y) { y.negate(); }
+    (x,y)
+}
+
+/// Evaluate a polynomial given coefficients from the constant term upwards.
+fn horner(coefficients: &[Fq2], x: &Fq2) -> Fq2 {
+    coefficients.iter().rev().fold(Fq2::zero(), |mut acc,k| {
+        acc.mul_assign(x);
+        acc.add_assign(k);
+        acc
+    })
+}
+
+/// RFC 9380 `iso_map` from `E'` to `E`, or `None` for the identity.
+fn iso_map(c: &Constants, x: &Fq2, y: &Fq2) -> Option<(Fq2,Fq2)> {
+    let mut x_out = horner(&c.x_num, x);
+    x_out.mul_assign(&horner(&c.x_den, x).inverse() ?);
+    let mut y_out = horner(&c.y_num, x);
+    y_out.mul_assign(&horner(&c.y_den, x).inverse() ?);
+    y_out.mul_assign(y);
+    Some((x_out,y_out))
+}
+
+/// Build a G2 point from affine coordinates already known to lie on `E`.
+fn from_coordinates(x: &Fq2, y: &Fq2) -> G2 {
+    let mut encoded = G2Uncompressed::empty();
+    {
+        let mut writer = encoded.as_mut();
+        for coordinate in [x.c1, x.c0, y.c1, y.c0].iter() {
+            coordinate.into_repr().write_be(&mut writer).unwrap();
+        }
+    }
+    encoded.into_affine_unchecked()
+        .expect("iso_map outputs valid coordinates")
+        .into_projective()
+}
+
+fn map_to_curve(c: &Constants, u: &Fq2) -> G2 {
+    let (x,y) = map_to_curve_simple_swu(c,u);
+    match iso_map(c,&x,&y) {
+        Some((x,y)) => from_coordinates(&x,&y),
+        None => G2::zero(),
+    }
+}
+
+/// Multiply by `h_eff` using double-and-add over its hex digits.
+fn clear_cofactor(p: &G2) -> G2 {
+    let mut acc = G2::zero();
+    for digit in H_EFF.chars() {
+        let digit = digit.to_digit(16).unwrap();
+        for i in (0..4).rev() {
+            acc.double();
+            if (digit >> i) & 1 == 1 { acc.add_assign(p); }
+        }
+    }
+    acc
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng};
+
+    use super::*;
+    use super::super::*;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}",b)).collect()
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i+2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn expand_message_xmd_vectors() {
+        // RFC 9380 appendix K.1
+        let dst = b"QUUX-V01-CS02-with-expander-SHA256-128";
+        assert_eq!( to_hex(&expand_message_xmd(b"", dst, 0x20)),
+            "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235" );
+        assert_eq!( to_hex(&expand_message_xmd(b"abc", dst, 0x20)),
+            "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615" );
+        assert_eq!( to_hex(&expand_message_xmd(b"", dst, 0x80)),
+            "af84c27ccfd45d41914fdff5df25293e221afc53d8ad2ac06d5e3e29485dadbe\
+             e0d121587713a3e0dd4d5e69e93eb7cd4f5df4cd103e188cf60cb02edc3edf18\
+             eda8576c412b18ffb658e3dd6ec849469b979d444cf7b26911a08e63cf31f9dc\
+             c541708d3491184472c2c29bb749d4286b004ceb5ee6b9a7fa5b646c993f0ced" );
+    }
+
+    #[test]
+    fn hash_to_g2_vectors() {
+        // RFC 9380 appendix J.10.1, as uncompressed ZCash encodings
+        // x.c1 || x.c0 || y.c1 || y.c0
+        let dst = b"QUUX-V01-CS02-with-BLS12381G2_XMD:SHA-256_SSWU_RO_";
+        let vectors : [(&[u8],&str); 3] = [
+            (b"", "05cb8437535e20ecffaef7752baddf98034139c38452458baeefab379ba13dff5bf5dd71b72418717047f5b0f37da03d\
+                   0141ebfbdca40eb85b87142e130ab689c673cf60f1a3e98d69335266f30d9b8d4ac44c1038e9dcdd5393faf5c41fb78a\
+                   12424ac32561493f3fe3c260708a12b7c620e7be00099a974e259ddc7d1f6395c3c811cdd19f1e8dbf3e9ecfdcbab8d6\
+                   0503921d7f6a12805e72940b963c0cf3471c7b2a524950ca195d11062ee75ec076daf2d4bc358c4b190c0c98064fdd92"),
+            (b"abc", "139cddbccdc5e91b9623efd38c49f81a6f83f175e80b06fc374de9eb4b41dfe4ca3a230ed250fbe3a2acf73a41177fd8\
+                      02c2d18e033b960562aae3cab37a27ce00d80ccd5ba4b7fe0e7a210245129dbec7780ccc7954725f4168aff2787776e6\
+                      00aa65dae3c8d732d10ecd2c50f8a1baf3001578f71c694e03866e9f3d49ac1e1ce70dd94a733534f106d4cec0eddd16\
+                      1787327b68159716a37440985269cf584bcb1e621d3a7202be6ea05c4cfe244aeb197642555a0645fb87bf7466b2ba48"),
+            (b"abcdef0123456789", "190d119345b94fbd15497bcba94ecf7db2cbfd1e1fe7da034d26cbba169fb3968288b3fafb265f9ebd380512a71c3f2c\
+                                   121982811d2491fde9ba7ed31ef9ca474f0e1501297f68c298e9f4c0028add35aea8bb83d53c08cfc007c1e005723cd0\
+                                   0bb5e7572275c567462d91807de765611490205a941a5a6af3b1691bfe596c31225d3aabdf15faff860cb4ef17c7c3be\
+                                   05571a0f8d3c08d094576981f4a3b8eda0a8e771fcdcc8ecceaf1356a6acf17574518acb506e435b639353c2e14827c8"),
+        ];
+        for (msg,expected) in vectors.iter() {
+            let point = hash_to_g2(msg, dst).into_affine();
+            assert_eq!( to_hex(point.into_uncompressed().as_ref()), *expected );
+            // Decoding checks the point lies in the prime order subgroup.
+            assert!( point.into_uncompressed().into_affine().is_ok() );
+        }
+    }
+
+    #[test]
+    fn ietf_engine() {
+        let mut keypair = Keypair::<Ietf<PopDST>>::generate(thread_rng());
+        let signed = keypair.sign(&Context::new(b"ctx"),b"ietf message");
+        assert!( signed.verify() );
+        let msg = signed.message;
+        assert!( msg.hash_to_signature_curve::<Ietf<PopDST>>() == hash_to_g2(&msg.0, PopDST::DST) );
+        assert!( msg.hash_to_signature_curve::<Ietf<PopDST>>() != msg.hash_to_signature_curve::<Ietf<BasicDST>>() );
+        assert!( msg.hash_to_signature_curve::<Ietf<PopDST>>() != msg.hash_to_signature_curve::<ZBLS>() );
+    }
+
+    #[test]
+    fn ietf_signatures() {
+        // Ethereum 2.0 consensus spec BLS `sign` test vector.
+        let secret = from_hex("263dbd792f5b1be47ed85f8938c0f29586af0d3ac7b977f21c278fe1462040e3");
+        let message = [0x56u8; 32];
+        let expected = "882730e5d03f6b42c3abc26d3372625034e1d871b65a8a6b900a56dae22da98abbe1b68f85e49fe7652a55ec3d0591c2\
+                        0767677e33e5cbb1207315c41a9ac03be39c2e7668edc043d6cb1d9fd93033caa8a1c5b0e84bedaeb6c64972503a43eb";
+
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&secret);
+        bytes.reverse();  // `from_bytes` expects little endian
+        let secret = SecretKeyVT::<Ietf<PopDST>>::from_bytes(&bytes).unwrap();
+        let publickey = secret.into_public();
+        let signature = secret.sign_ietf(&message);
+        assert_eq!( to_hex(signature.0.into_affine().into_compressed().as_ref()), expected );
+        assert!( publickey.verify_ietf(&message, &signature) );
+        assert!( ! publickey.verify_ietf(b"other message", &signature) );
+
+        let mut split = secret.into_split(thread_rng());
+        assert!( split.sign_ietf(&message, thread_rng()) == signature );
+        // Our usual context hashing yields a different, non standard, signature.
+        assert!( secret.sign(&Context::new(b""), &message) != signature );
+        assert!( ! PublicKey::<Ietf<BasicDST>>(publickey.0).verify_ietf(&message, &Signature(signature.0)) );
+    }
+}
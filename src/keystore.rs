@@ -0,0 +1,434 @@
+//! ## EIP-2335 JSON keystores
+//!
+//! Ethereum 2.0 validator tooling stores BLS12-381 secret keys in the
+//! JSON keystore format specified by EIP-2335, see
+//! https://eips.ethereum.org/EIPS/eip-2335
+//!
+//! A keystore's `crypto` section has three modules:  A `kdf` module
+//! derives a 32 byte decryption key from the password, using either
+//! `pbkdf2` with `hmac-sha256` or else `scrypt`.  A `checksum` module
+//! holds the SHA256 hash of the second half of the decryption key and
+//! the ciphertext, which lets us reject an incorrect password without
+//! decrypting anything.  A `cipher` module holds the big endian secret
+//! key encrypted using `aes-128-ctr` under the first half of the
+//! decryption key.
+//!
+//! We process passwords as EIP-2335 requires, first by applying
+//! the NFKD unicode normalization, and then stripping all control
+//! codes, so that visually identical passwords decrypt identically.
+//!
+//! We support only BLS12-381 secret keys here, for either orientation,
+//! but the eth2 public key in the `pubkey` field assumes `UsualBLS`
+//! orientation, so `TinyBLS` keystores are unlikely to interoperate.
+
+use aes::Aes128;
+use ctr::Ctr128BE;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use ff::PrimeFieldDecodingError;
+use hmac::Hmac;
+use rand::Rng;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::*;
+use super::codec::Eth2Codec;
+
+
+/// Number of `pbkdf2` iterations used by `to_json`, as suggested
+/// by EIP-2335.
+pub const PBKDF2_ITERATIONS: u32 = 262144;
+
+/// Largest `pbkdf2` iteration count `from_json` accepts, so that
+/// an untrusted keystore cannot make decryption run indefinitely.
+pub const MAX_PBKDF2_ITERATIONS: u64 = 1 << 22;
+
+/// Largest `scrypt` cost `128 n r p` in bytes that `from_json` accepts,
+/// which bounds both the memory and time an untrusted keystore consumes.
+pub const MAX_SCRYPT_COST: u64 = 1 << 30;
+
+/// Errors from loading an EIP-2335 keystore.
+#[derive(Debug)]
+pub enum KeystoreError {
+    /// Our JSON input did not parse.
+    Json(::serde_json::Error),
+    /// Our JSON parsed, but some required field was missing or invalid.
+    Malformed(&'static str),
+    /// The keystore used some key derivation, checksum, or cipher
+    /// function unspecified by EIP-2335.
+    Unsupported(&'static str),
+    /// The checksum failed, so either the password was incorrect
+    /// or else the keystore was corrupted.
+    InvalidPassword,
+    /// The decrypted secret key was not a canonical scalar.
+    InvalidSecretKey(PrimeFieldDecodingError),
+    /// The decrypted secret key does not match the `pubkey` field.
+    MismatchedPublicKey,
+}
+
+impl ::std::fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        use self::KeystoreError::*;
+        match self {
+            Json(e) => write!(f, "Keystore JSON did not parse: {}", e),
+            Malformed(s) => write!(f, "Malformed keystore: {}", s),
+            Unsupported(s) => write!(f, "Unsupported keystore function: {}", s),
+            InvalidPassword => write!(f, "Keystore checksum failed, likely due to an incorrect password."),
+            InvalidSecretKey(e) => write!(f, "Keystore contains an invalid secret key: {}", e),
+            MismatchedPublicKey => write!(f, "Keystore secret key does not match its public key."),
+        }
+    }
+}
+
+impl ::std::error::Error for KeystoreError {
+    fn description(&self) -> &str {
+        use self::KeystoreError::*;
+        match self {
+            Json(_) => "Keystore JSON did not parse.",
+            Malformed(s) => s,
+            Unsupported(s) => s,
+            InvalidPassword => "Keystore checksum failed, likely due to an incorrect password.",
+            InvalidSecretKey(_) => "Keystore contains an invalid secret key.",
+            MismatchedPublicKey => "Keystore secret key does not match its public key.",
+        }
+    }
+}
+
+impl From<::serde_json::Error> for KeystoreError {
+    fn from(e: ::serde_json::Error) -> KeystoreError { KeystoreError::Json(e) }
+}
+
+/// Normalize and strip control codes from a password, as EIP-2335 requires.
+fn process_password(password: &str) -> Vec<u8> {
+    password.nfkd().filter(|c| ! c.is_control()).collect::<String>().into_bytes()
+}
+
+fn field<'a>(value: &'a Value, name: &'static str) -> Result<&'a Value,KeystoreError> {
+    value.get(name).ok_or(KeystoreError::Malformed(name))
+}
+
+fn str_field<'a>(value: &'a Value, name: &'static str) -> Result<&'a str,KeystoreError> {
+    field(value,name) ?.as_str().ok_or(KeystoreError::Malformed(name))
+}
+
+fn u64_field(value: &Value, name: &'static str) -> Result<u64,KeystoreError> {
+    field(value,name) ?.as_u64().ok_or(KeystoreError::Malformed(name))
+}
+
+fn u32_field(value: &Value, name: &'static str) -> Result<u32,KeystoreError> {
+    ::std::convert::TryFrom::try_from(u64_field(value,name) ?)
+        .map_err(|_| KeystoreError::Malformed(name))
+}
+
+fn hex_field(value: &Value, name: &'static str) -> Result<Vec<u8>,KeystoreError> {
+    ::hex::decode(str_field(value,name) ?).map_err(|_| KeystoreError::Malformed(name))
+}
+
+/// Derive the 32 byte decryption key from the `kdf` module.
+///
+/// We reject `pbkdf2` and `scrypt` parameters beyond `MAX_PBKDF2_ITERATIONS`
+/// and `MAX_SCRYPT_COST`, respectively.
+fn derive_key(kdf: &Value, password: &[u8]) -> Result<Zeroizing<[u8; 32]>,KeystoreError> {
+    let params = field(kdf,"params") ?;
+    if u64_field(params,"dklen") ? != 32 {
+        return Err(KeystoreError::Unsupported("dklen other than 32"));
+    }
+    let salt = hex_field(params,"salt") ?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    match str_field(kdf,"function") ? {
+        "pbkdf2" => {
+            if str_field(params,"prf") ? != "hmac-sha256" {
+                return Err(KeystoreError::Unsupported("pbkdf2 prf other than hmac-sha256"));
+            }
+            let c = u64_field(params,"c") ?;
+            if c == 0 || c > MAX_PBKDF2_ITERATIONS {
+                return Err(KeystoreError::Unsupported("pbkdf2 c out of range"));
+            }
+            ::pbkdf2::pbkdf2::<Hmac<Sha256>>(password, &salt, c as usize, &mut *key);
+        },
+        "scrypt" => {
+            let n = u64_field(params,"n") ?;
+            if ! n.is_power_of_two() || n < 2 {
+                return Err(KeystoreError::Malformed("scrypt n must be a power of two"));
+            }
+            let r = u32_field(params,"r") ?;
+            let p = u32_field(params,"p") ?;
+            let cost = 128u64.checked_mul(n)
+                .and_then(|c| c.checked_mul(r as u64))
+                .and_then(|c| c.checked_mul(p as u64));
+            if ! cost.is_some_and(|c| c <= MAX_SCRYPT_COST) {
+                return Err(KeystoreError::Unsupported("scrypt cost out of range"));
+            }
+            let params = ::scrypt::ScryptParams::new(n.trailing_zeros() as u8, r, p)
+                .map_err(|_| KeystoreError::Malformed("scrypt parameters"))?;
+            ::scrypt::scrypt(password, &salt, &params, &mut *key)
+                .expect("32 bytes is a valid scrypt output length");
+        },
+        _ => return Err(KeystoreError::Unsupported("kdf")),
+    }
+    Ok(key)
+}
+
+fn checksum(key: &[u8; 32], ciphertext: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.input(&key[16..32]);
+    hasher.input(ciphertext);
+    hasher.result().to_vec()
+}
+
+fn aes_128_ctr(key: &[u8; 32], iv: &[u8], data: &mut [u8]) {
+    let mut cipher = Ctr128BE::<Aes128>::new(key[0..16].into(), iv.into());
+    cipher.apply_keystream(data);
+}
+
+/// Decrypt the secret key from an EIP-2335 JSON keystore.
+///
+/// We check the checksum before decrypting, so an incorrect password
+/// yields `KeystoreError::InvalidPassword`.  If the keystore contains
+/// a nonempty `pubkey` field, then we also check that it matches
+/// the decrypted secret key.  We erase the processed password and
+/// the derived decryption key before returning.
+pub fn from_json<E>(json: &str, password: &str) -> Result<SecretKey<E>,KeystoreError>
+where E: UnmutatedKeys<Scalar = ::pairing::bls12_381::Fr>
+{
+    let keystore: Value = ::serde_json::from_str(json) ?;
+    if u64_field(&keystore,"version") ? != 4 {
+        return Err(KeystoreError::Unsupported("keystore version other than 4"));
+    }
+    let crypto = field(&keystore,"crypto") ?;
+    let kdf = field(crypto,"kdf") ?;
+    let checksum_module = field(crypto,"checksum") ?;
+    let cipher = field(crypto,"cipher") ?;
+    if str_field(checksum_module,"function") ? != "sha256" {
+        return Err(KeystoreError::Unsupported("checksum"));
+    }
+    if str_field(cipher,"function") ? != "aes-128-ctr" {
+        return Err(KeystoreError::Unsupported("cipher"));
+    }
+    let iv = hex_field(field(cipher,"params") ?,"iv") ?;
+    if iv.len() != 16 {
+        return Err(KeystoreError::Malformed("iv"));
+    }
+    let mut secret = hex_field(cipher,"message") ?;
+    if secret.len() != 32 {
+        return Err(KeystoreError::Malformed("cipher message"));
+    }
+
+    let mut password = process_password(password);
+    let key = derive_key(kdf, &password);
+    password.zeroize();
+    let key = key ?;
+    if checksum(&key,&secret) != hex_field(checksum_module,"message") ? {
+        return Err(KeystoreError::InvalidPassword);
+    }
+    aes_128_ctr(&key, &iv, &mut secret);
+
+    // EIP-2335 stores secret keys big endian, but `from_bytes` expects little endian.
+    let mut bytes = *array_ref![secret,0,32];
+    bytes.reverse();
+    let result = SecretKey::from_bytes(&bytes).map_err(KeystoreError::InvalidSecretKey);
+    // We erase our plaintext copies, whether or not they were a valid key.
+    secret.zeroize();
+    bytes.zeroize();
+    let secret = result ?;
+
+    if let Some(pubkey) = keystore.get("pubkey").and_then(Value::as_str) {
+        if ! pubkey.is_empty() && pubkey != ::hex::encode(secret.into_public().encode::<Eth2Codec>()) {
+            return Err(KeystoreError::MismatchedPublicKey);
+        }
+    }
+    Ok(secret)
+}
+
+/// Encrypt a secret key into an EIP-2335 JSON keystore.
+///
+/// We derive the encryption key using `pbkdf2` with `hmac-sha256` and
+/// `PBKDF2_ITERATIONS` iterations, along with a fresh random salt, iv,
+/// and version 4 uuid.
+pub fn to_json<E,R>(secret: &SecretKey<E>, password: &str, mut rng: R) -> String
+where E: UnmutatedKeys<Scalar = ::pairing::bls12_381::Fr>, R: Rng
+{
+    let salt = rng.gen::<[u8; 32]>();
+    let iv = rng.gen::<[u8; 16]>();
+    let mut uuid = rng.gen::<[u8; 16]>();
+    uuid[6] = (uuid[6] & 0x0f) | 0x40;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    let uuid = ::hex::encode(uuid);
+    let uuid = format!("{}-{}-{}-{}-{}", &uuid[0..8], &uuid[8..12], &uuid[12..16], &uuid[16..20], &uuid[20..32]);
+
+    let mut password = process_password(password);
+    let mut key = Zeroizing::new([0u8; 32]);
+    ::pbkdf2::pbkdf2::<Hmac<Sha256>>(&password, &salt, PBKDF2_ITERATIONS as usize, &mut *key);
+    password.zeroize();
+
+    let mut ciphertext = secret.to_bytes();
+    ciphertext.reverse();  // EIP-2335 stores secret keys big endian
+    aes_128_ctr(&key, &iv, &mut ciphertext);
+
+    json!({
+        "crypto": {
+            "kdf": {
+                "function": "pbkdf2",
+                "params": {
+                    "dklen": 32,
+                    "c": PBKDF2_ITERATIONS,
+                    "prf": "hmac-sha256",
+                    "salt": ::hex::encode(salt),
+                },
+                "message": "",
+            },
+            "checksum": {
+                "function": "sha256",
+                "params": {},
+                "message": ::hex::encode(checksum(&key,&ciphertext)),
+            },
+            "cipher": {
+                "function": "aes-128-ctr",
+                "params": { "iv": ::hex::encode(iv) },
+                "message": ::hex::encode(ciphertext),
+            },
+        },
+        "description": "",
+        "pubkey": ::hex::encode(secret.into_public().encode::<Eth2Codec>()),
+        "path": "",
+        "uuid": uuid,
+        "version": 4,
+    }).to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng};  // Rng
+
+    use super::*;
+
+    /// The pbkdf2 test vector from EIP-2335.
+    const PBKDF2_KEYSTORE: &str = r#"{
+        "crypto": {
+            "kdf": {
+                "function": "pbkdf2",
+                "params": {
+                    "dklen": 32,
+                    "c": 262144,
+                    "prf": "hmac-sha256",
+                    "salt": "d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3"
+                },
+                "message": ""
+            },
+            "checksum": {
+                "function": "sha256",
+                "params": {},
+                "message": "8a9f5d9912ed7e75ea794bc5a89bca5f193721d30868ade6f73043c6ea6febf1"
+            },
+            "cipher": {
+                "function": "aes-128-ctr",
+                "params": {
+                    "iv": "264daa3f303d7259501c93d997d84fe6"
+                },
+                "message": "cee03fde2af33149775b7223e7845e4fb2c8ae1792e5f99fe9ecf474cc8c16ad"
+            }
+        },
+        "description": "This is a test keystore that uses PBKDF2 to secure the secret.",
+        "pubkey": "9612d7a727c9d0a22e185a1c768478dfe919cada9266988cb32359c11f2b7b27f4ae4040902382ae2910c15e2b420d07",
+        "path": "m/12381/60/0/0",
+        "uuid": "64625def-3331-4eea-ab6f-782f3ed16a83",
+        "version": 4
+    }"#;
+
+    /// The scrypt test vector from EIP-2335.
+    const SCRYPT_KEYSTORE: &str = r#"{
+        "crypto": {
+            "kdf": {
+                "function": "scrypt",
+                "params": {
+                    "dklen": 32,
+                    "n": 262144,
+                    "p": 1,
+                    "r": 8,
+                    "salt": "d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3"
+                },
+                "message": ""
+            },
+            "checksum": {
+                "function": "sha256",
+                "params": {},
+                "message": "d2217fe5f3e9a1e34581ef8a78f7c9928e436d36dacc5e846690a5581e8ea484"
+            },
+            "cipher": {
+                "function": "aes-128-ctr",
+                "params": {
+                    "iv": "264daa3f303d7259501c93d997d84fe6"
+                },
+                "message": "06ae90d55fe0a6e9c5c3bc5b170827b2e5cce3929ed3f116c2811e6366dfe20f"
+            }
+        },
+        "description": "This is a test keystore that uses scrypt to secure the secret.",
+        "pubkey": "9612d7a727c9d0a22e185a1c768478dfe919cada9266988cb32359c11f2b7b27f4ae4040902382ae2910c15e2b420d07",
+        "path": "m/12381/60/3141592653/589793238",
+        "uuid": "1d85ae20-35c5-4611-98e8-aa14a633906f",
+        "version": 4
+    }"#;
+
+    const PASSWORD: &str = "𝔱𝔢𝔰𝔱𝔭𝔞𝔰𝔰𝔴𝔬𝔯𝔡🔑";
+
+    #[test]
+    fn eip2335_test_vector() {
+        let secret = from_json::<ZBLS>(PBKDF2_KEYSTORE, PASSWORD).unwrap();
+        let mut expected = ::hex::decode("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f").unwrap();
+        expected.reverse();
+        assert!( secret.to_bytes()[..] == expected[..] );
+
+        match from_json::<ZBLS>(PBKDF2_KEYSTORE, "testpassword") {
+            Err(KeystoreError::InvalidPassword) => {},
+            _ => panic!("Incorrect password not rejected by checksum"),
+        }
+        // NFKD normalization maps the fraktur password to plain ascii, and we strip control codes.
+        assert!( from_json::<ZBLS>(PBKDF2_KEYSTORE, "testpassword\u{7f}🔑").is_ok() );
+    }
+
+    #[test]
+    fn eip2335_scrypt_test_vector() {
+        let secret = from_json::<ZBLS>(SCRYPT_KEYSTORE, PASSWORD).unwrap();
+        let pbkdf2 = from_json::<ZBLS>(PBKDF2_KEYSTORE, PASSWORD).unwrap();
+        assert!( secret.to_bytes() == pbkdf2.to_bytes() );
+        match from_json::<ZBLS>(SCRYPT_KEYSTORE, "testpassword") {
+            Err(KeystoreError::InvalidPassword) => {},
+            _ => panic!("Incorrect password not rejected by checksum"),
+        }
+    }
+
+    #[test]
+    fn kdf_parameter_bounds() {
+        let huge_c = PBKDF2_KEYSTORE.replace("\"c\": 262144", "\"c\": 4294967296");
+        match from_json::<ZBLS>(&huge_c, PASSWORD) {
+            Err(KeystoreError::Unsupported(_)) => {},
+            _ => panic!("Excessive pbkdf2 iterations not rejected"),
+        }
+        let huge_n = SCRYPT_KEYSTORE.replace("\"n\": 262144", "\"n\": 1073741824");
+        match from_json::<ZBLS>(&huge_n, PASSWORD) {
+            Err(KeystoreError::Unsupported(_)) => {},
+            _ => panic!("Excessive scrypt n not rejected"),
+        }
+        let huge_r = SCRYPT_KEYSTORE.replace("\"r\": 8", "\"r\": 4294967304");
+        match from_json::<ZBLS>(&huge_r, PASSWORD) {
+            Err(KeystoreError::Malformed("r")) => {},
+            _ => panic!("Truncated scrypt r not rejected"),
+        }
+    }
+
+    #[test]
+    fn keystore_round_trip() {
+        let keypair = Keypair::<ZBLS>::generate(thread_rng());
+        let json = to_json(&keypair.secret, PASSWORD, thread_rng());
+        let secret = from_json::<ZBLS>(&json, PASSWORD).unwrap();
+        assert!( secret.to_bytes() == keypair.secret.to_bytes() );
+        let context = Context::new(b"ctx");
+        assert!( keypair.public.verify(&context, b"test message", &secret.into_vartime().sign(&context, b"test message")) );
+
+        assert!( from_json::<ZBLS>(&json, "wrong password").is_err() );
+        let tampered = json.replace("\"version\":4", "\"version\":3");
+        assert!( from_json::<ZBLS>(&tampered, PASSWORD).is_err() );
+    }
+}
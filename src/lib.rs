@@ -75,7 +75,12 @@
 //!
 //! As a rule, we also attempt to batch normalize different arithmatic
 //! outputs, but concievably small signer set sizes might make this
-//! a pessimization.
+//! a pessimization, so verifiers only batch normalize above
+//! `verifiers::BATCH_NORMALIZE_THRESHOLD` points, which callers may
+//! override using `verifiers::VerifierConfig`.
+//!
+//! We do not yet support `no_std`, because our `ff` and `paired`
+//! dependencies require `std`, so no `no_std` build could link.
 //!
 //! 
 //!
@@ -83,6 +88,7 @@
 
 // #![feature(generic_associated_types)]
 #![feature(associated_type_defaults)]
+#![cfg_attr(test, feature(test))]
 
 #[macro_use]
 extern crate arrayref;
@@ -92,28 +98,63 @@ extern crate ff;
 
 extern crate paired as pairing;
 extern crate rand;
+extern crate sha2;
 extern crate sha3;
+extern crate hkdf;
 
 #[cfg(feature = "serde")]
 extern crate serde;
 
+#[cfg(feature = "keystore")]
+extern crate aes;
+#[cfg(feature = "keystore")]
+extern crate ctr;
+#[cfg(feature = "keystore")]
+extern crate hex;
+#[cfg(feature = "keystore")]
+extern crate hmac;
+#[cfg(feature = "keystore")]
+extern crate pbkdf2;
+#[cfg(feature = "keystore")]
+extern crate scrypt;
+#[cfg(feature = "keystore")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "keystore")]
+extern crate unicode_normalization;
+
+#[cfg(feature = "parallel")]
+extern crate rayon;
+
+#[cfg(feature = "zeroize")]
+extern crate zeroize;
+
+#[cfg(test)]
+extern crate test;
+
 
 use std::borrow::Borrow;
 
 
 pub mod engine;
 pub mod single;
+pub mod codec;
+pub mod ietf;
 pub mod distinct;
 pub mod pop;
 pub mod bit;
 pub mod delinear;
+pub mod optimal;
 pub mod verifiers;
+#[cfg(feature = "keystore")]
+pub mod keystore;
 // pub mod delinear;
 
 pub use engine::*;
+pub use ietf::Ietf;
 
-pub use single::{PublicKey,KeypairVT,Keypair,SecretKeyVT,SecretKey,Signature};
-pub use bit::{BitSignedMessage,CountSignedMessage};
+pub use single::{PublicKey,PreparedPublicKey,KeypairVT,Keypair,SecretKeyVT,SecretKey,Signature};
+pub use bit::{BitSignedMessage,CountSignedMessage,IndexSignedMessage};
 
 
 /// Internal message hash size.  
@@ -127,11 +168,37 @@ const MESSAGE_SIZE: usize = 32;
 #[derive(Debug,Copy,Clone,Hash,PartialEq,Eq,PartialOrd,Ord)]
 pub struct Message(pub [u8; MESSAGE_SIZE]);
 
-impl Message {
-    pub fn new(context: &[u8], message: &[u8]) -> Message {
-        use sha3::{Shake128, digest::{Input,ExtendableOutput,XofReader}};
-        let mut h = Shake128::default();
+/// Domain separation context for hashing messages.
+///
+/// We construct a `Context` once per protocol, which absorbs its
+/// domain separation string, and then derive every `Message` from it,
+/// so signers and verifiers cannot disagree about the context, nor
+/// forget it entirely.  Signing with one `Context` and verifying
+/// a message from another `Context` fails.
+#[derive(Clone)]
+pub struct Context(::sha3::Shake128);
+
+impl ::std::fmt::Debug for Context {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        // The hasher state is opaque, so show a digest of it instead.
+        let m = Message::new(self,b"");
+        write!(f, "Context({:?})", &m.0[..16])
+    }
+}
+
+impl Context {
+    pub fn new(context: &[u8]) -> Context {
+        use sha3::digest::Input;
+        let mut h = ::sha3::Shake128::default();
         h.input(context);
+        Context(h)
+    }
+}
+
+impl Message {
+    pub fn new(context: &Context, message: &[u8]) -> Message {
+        use sha3::digest::{Input,ExtendableOutput,XofReader};
+        let mut h = context.0.clone();
         let l = message.len() as u64;
         h.input(l.to_le_bytes());
         h.input(message);
@@ -149,7 +216,7 @@ impl Message {
 }
 
 impl<'a> From<&'a [u8]> for Message {
-    fn from(x: &[u8]) -> Message { Message::new(b"",x) }     
+    fn from(x: &[u8]) -> Message { Message::new(&Context::new(b""),x) }     
 }
 
 
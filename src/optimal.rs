@@ -0,0 +1,176 @@
+//! ## Automatic selection of an aggregation strategy
+//!
+//! We provide `aggregate_optimally` for callers who hold a batch of
+//! `SignedMessage`s and merely want the fastest secure aggregate,
+//! without choosing between our aggregation strategies themselves.
+//!
+//! We inspect the messages only, never the signers, because our
+//! linear strategies require invariants about the signers that we
+//! cannot check here, like previously verified proofs-of-possession.
+//! In particular, we never add public keys that signed the same
+//! message without delinearization, because doing so permits rogue
+//! key attacks unless the caller checked proofs-of-possession.
+//! Callers who checked proofs-of-possession should instead use the
+//! types in `pop` or `bit` directly.
+//!
+//! Our heuristics are:
+//! - If all messages are distinct, then we use `DistinctMessages`,
+//!   which requires no scalar multiplications and already merges
+//!   messages by the same signer during verification.  A single
+//!   `SignedMessage` counts as distinct messages.
+//! - If any message repeats, then distinct message aggregation is
+//!   impossible, so we use `Delinearized`, which merges all signers
+//!   of the same message into one public key, so verification costs
+//!   one pairing per distinct message, plus one for the signature.
+//!   If all messages are equal then verification therefore costs only
+//!   two pairings, regardless of the number of signers.
+//!
+//! We reject any `SignedMessage` by the identity public key, which
+//! verifies on any message without any secret key, before choosing
+//! a strategy, so both strategies refuse it alike.
+//!
+//! We return an `OptimalAggregate` enum, not a `Box<dyn Signed>`,
+//! because `Signed` is neither object safe nor implemented for owned
+//! aggregates, due to the absence of associated type constructors.
+
+use std::collections::HashSet;
+
+use pairing::CurveProjective;
+
+use super::*;
+use super::single::SignedMessage;
+use super::distinct::{DistinctMessages,DistinctMessagesError};
+use super::delinear::Delinearized;
+
+
+/// Aggregate BLS signature produced by `aggregate_optimally`.
+pub enum OptimalAggregate<E: EngineBLS> {
+    /// Some message repeated, so we delinearized all signers.
+    Delinearized(Box<Delinearized<E>>),
+    /// All messages were distinct.
+    Distinct(DistinctMessages<E>),
+}
+
+impl<'a,E: EngineBLS> Signed for &'a OptimalAggregate<E> {
+    type E = E;
+
+    type M = &'a Message;
+    type PKG = &'a PublicKey<Self::E>;
+    type PKnM = ::std::collections::hash_map::Iter<'a,Message,PublicKey<E>>;
+
+    fn messages_and_publickeys(self) -> Self::PKnM {
+        match *self {
+            OptimalAggregate::Delinearized(ref dl) => dl.messages_and_publickeys(),
+            OptimalAggregate::Distinct(ref dm) => dm.messages_and_publickeys(),
+        }
+    }
+
+    fn signature(&self) -> Signature<E> {
+        match **self {
+            OptimalAggregate::Delinearized(ref dl) => (&**dl).signature(),
+            OptimalAggregate::Distinct(ref dm) => dm.signature(),
+        }
+    }
+
+    fn verify(self) -> bool {
+        match *self {
+            OptimalAggregate::Delinearized(ref dl) => dl.verify(),
+            OptimalAggregate::Distinct(ref dm) => dm.verify(),
+        }
+    }
+}
+
+/// Aggregate signed messages using the most efficient secure strategy.
+///
+/// See the module level documentation for our heuristics.
+/// We return an error if any signer has the identity public key.
+pub fn aggregate_optimally<E: EngineBLS>(signed: &[SignedMessage<E>])
+ -> Result<OptimalAggregate<E>,DistinctMessagesError>
+{
+    if signed.iter().any(|s| s.publickey.0.is_zero()) {
+        return Err(DistinctMessagesError::IdentityPublicKey);
+    }
+    let mut messages = HashSet::with_capacity(signed.len());
+    if signed.iter().all(|s| messages.insert(s.message)) {
+        let dm = signed.iter()
+            .try_fold(DistinctMessages::new(), |dm,s| dm.add(s)) ?;
+        return Ok(OptimalAggregate::Distinct(dm));
+    }
+    let mut dl = Delinearized::new_batched();
+    for s in signed.iter() {
+        dl.add(s);
+    }
+    Ok(OptimalAggregate::Delinearized(Box::new(dl)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng};  // Rng
+
+    use super::*;
+
+    #[test]
+    fn optimal_strategy() {
+        let k = |_| Keypair::<ZBLS>::generate(thread_rng());
+        let mut keypairs = (0..4).into_iter().map(k).collect::<Vec<_>>();
+
+        let context = Context::new(b"ctx");
+        let sigs = keypairs.iter_mut().map(|k| k.sign(&context,b"same message")).collect::<Vec<_>>();
+        let agg = aggregate_optimally(&sigs).unwrap();
+        match agg {
+            OptimalAggregate::Delinearized(_) => {},
+            _ => panic!("Identical messages did not use same message aggregation"),
+        }
+        assert!( agg.messages_and_publickeys().len() == 1 );
+        assert!( agg.verify() );
+        assert!( verifiers::verify_simple(&agg) );
+
+        let sigs = keypairs.iter_mut().enumerate()
+            .map(|(i,k)| k.sign(&context,&[i as u8]))
+            .collect::<Vec<_>>();
+        let agg = aggregate_optimally(&sigs).unwrap();
+        match agg {
+            OptimalAggregate::Distinct(_) => {},
+            _ => panic!("Distinct messages did not use distinct message aggregation"),
+        }
+        assert!( agg.messages_and_publickeys().len() == 4 );
+        assert!( agg.verify() );
+        assert!( verifiers::verify_simple(&agg) );
+
+        let mut mixed = sigs.clone();
+        mixed.push(keypairs[0].sign(&context,&[1u8]));
+        let agg = aggregate_optimally(&mixed).unwrap();
+        match agg {
+            OptimalAggregate::Delinearized(_) => {},
+            _ => panic!("Repeated messages did not use delinearized aggregation"),
+        }
+        assert!( agg.verify() );
+
+        let mut bad = sigs.clone();
+        bad[1].signature = bad[2].signature;
+        assert!( ! aggregate_optimally(&bad).unwrap().verify() );
+    }
+
+    #[test]
+    fn identity_publickey() {
+        // The identity public key verifies the identity signature on any message.
+        let forged = SignedMessage::<ZBLS> {
+            message: Message::new(&Context::new(b"ctx"),b"never signed"),
+            publickey: PublicKey(<ZBLS as EngineBLS>::PublicKeyGroup::zero()),
+            signature: Signature(<ZBLS as EngineBLS>::SignatureGroup::zero()),
+        };
+        assert!( forged.verify() );
+        let is_identity = |r: Result<OptimalAggregate<ZBLS>,DistinctMessagesError>|
+            matches!(r, Err(DistinctMessagesError::IdentityPublicKey));
+
+        assert!( is_identity(aggregate_optimally(&[forged.clone()])) );
+        let mut keypair = Keypair::<ZBLS>::generate(thread_rng());
+        let signed = keypair.sign(&Context::new(b"ctx"),b"signed");
+        assert!( is_identity(aggregate_optimally(&[signed.clone(), forged.clone()])) );
+        let mut repeated = forged.clone();
+        repeated.message = signed.message;
+        assert!( is_identity(aggregate_optimally(&[signed.clone(), repeated])) );
+        assert!( aggregate_optimally(&[signed]).unwrap().verify() );
+    }
+}
@@ -22,6 +22,14 @@
 //! so proofs-of-possession help with aggregating votes in a concensus
 //! protocol, but should never be used for accounts on a block chain.
 //!
+//! We create and check proofs-of-possession with
+//! `SecretKey::prove_possession` and `PublicKey::verify_possession`.
+//! We sign the public key under a domain separation distinct from
+//! every `Message`, so proofs-of-possession can never be replayed
+//! as ordinary signatures.  After checking a proof-of-possession, you
+//! obtain a key usable with this module by calling
+//! `PublicKey::i_have_checked_this_proof_of_possession`.
+//!
 //! We assume here that users provide their own data structure for
 //! proofs-of-poossession.  We provide more structure for users who
 //! one bit per vote in a concensus protocol:  
@@ -37,12 +45,114 @@
 
 use std::borrow::{Borrow}; // BorrowMut
 use std::collections::HashMap;
+use std::iter::once;
 
-use pairing::{CurveProjective}; // CurveAffine, Engine
+use pairing::{CurveAffine, CurveProjective}; // Engine
 
 use super::*;
-use super::verifiers::verify_with_distinct_messages;
+use super::single::SignedMessage;
+use super::distinct::{Contributions,IdentityPublicKey,UnknownContribution};
+use super::verifiers::{verify_simple,verify_with_distinct_messages};
+
+
+/// Domain separation for the self-signed certificates used as
+/// proofs-of-possession.
+const PROOF_OF_POSSESSION_CONTEXT: &[u8] = b"BLS proof-of-possession";
+
+/// Hash a public key to the signature curve for its proof-of-possession.
+///
+/// We hash more than `MESSAGE_SIZE` bytes here, so our input to
+/// `EngineBLS::hash_to_signature_curve` never equals the input from
+/// any `Message`, and thus proofs-of-possession can never be replayed
+/// as signatures on messages, regardless of their context.
+fn hash_publickey_to_signature_curve<E: EngineBLS>(publickey: &PublicKey<E>) -> E::SignatureGroup {
+    let mut bytes = PROOF_OF_POSSESSION_CONTEXT.to_vec();
+    bytes.extend_from_slice(publickey.0.into_affine().into_uncompressed().as_ref());
+    E::hash_to_signature_curve(&bytes[..])
+}
+
+/// Proof-of-possession of the secret key for some public key,
+/// which consists of a self-signed certificate on the public key.
+#[derive(Debug)]
+pub struct ProofOfPossession<E: EngineBLS>(pub E::SignatureGroup);
+
+impl<E: EngineBLS> Clone for ProofOfPossession<E> {
+    fn clone(&self) -> Self { *self }
+}
+impl<E: EngineBLS> Copy for ProofOfPossession<E> { }
+
+impl<E: EngineBLS>  PartialEq<Self> for ProofOfPossession<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq(&other.0)
+    }
+}
+
+impl <E: EngineBLS> Eq for ProofOfPossession<E> {}
+
+impl<E: EngineBLS> SecretKeyVT<E> {
+    /// Create a proof-of-possession for our public key without
+    /// side channel protections.
+    pub fn prove_possession(&self) -> ProofOfPossession<E> {
+        let mut s = hash_publickey_to_signature_curve(&self.into_public());
+        s.mul_assign(self.0);
+        ProofOfPossession(s)
+    }
+}
 
+impl<E: EngineBLS> SecretKey<E> {
+    /// Create a proof-of-possession for our public key.
+    ///
+    /// We multiply by both key halves in constant time, but do not
+    /// resplit for side channel protections here since this call
+    /// should be rare, much like `into_public`.
+    pub fn prove_possession(&self) -> ProofOfPossession<E> {
+        let point = hash_publickey_to_signature_curve(&self.into_public());
+        ProofOfPossession(self.mul_point_ct(&point))
+    }
+}
+
+impl<E: EngineBLS> PublicKey<E> {
+    /// Check a proof-of-possession for this public key.
+    ///
+    /// If this succeeds then you may safely call
+    /// `i_have_checked_this_proof_of_possession` on this key. 
+    ///
+    /// We reject the identity public key, whose identity proof
+    /// verifies trivially, despite proving no secret key.
+    pub fn verify_possession(&self, proof: &ProofOfPossession<E>) -> bool {
+        if self.0.is_zero() { return false; }
+        let publickey = self.0.into_affine().prepare();
+        let message = hash_publickey_to_signature_curve(self).into_affine().prepare();
+        let signature = proof.0.into_affine().prepare();
+        E::verify_prepared( & signature, once((&publickey,&message)) )
+    }
+}
+
+
+/// Sum public keys, as required by aggregation under proofs-of-possession.
+///
+/// With the `parallel` feature, we split the additions across rayon's
+/// global thread pool, which helps verifiers servicing large committees.
+#[cfg(not(feature = "parallel"))]
+pub fn sum_publickeys<E: EngineBLS>(publickeys: &[PublicKey<E>]) -> PublicKey<E> {
+    let mut publickey = E::PublicKeyGroup::zero();
+    for pk in publickeys.iter() {
+        publickey.add_assign(&pk.0);
+    }
+    PublicKey(publickey)
+}
+
+/// Sum public keys, as required by aggregation under proofs-of-possession.
+///
+/// With the `parallel` feature, we split the additions across rayon's
+/// global thread pool, which helps verifiers servicing large committees.
+#[cfg(feature = "parallel")]
+pub fn sum_publickeys<E: EngineBLS>(publickeys: &[PublicKey<E>]) -> PublicKey<E> {
+    use rayon::prelude::*;
+    PublicKey(publickeys.par_iter()
+        .map(|pk| pk.0)
+        .reduce(E::PublicKeyGroup::zero, |mut x,y| { x.add_assign(&y); x }))
+}
 
 /// Batch or aggregate BLS signatures with attached messages and
 /// signers, for whom we previously checked proofs-of-possession.
@@ -80,16 +190,28 @@ use super::verifiers::verify_with_distinct_messages;
 // but this sounds complex or worse fragile.
 //
 // TODO: Implement gaussian elimination verification scheme.
-#[derive(Clone)]
+// #[derive(Clone)]
 pub struct BatchAssumingProofsOfPossession<E: EngineBLS> {
     messages_n_publickeys: HashMap<Message,PublicKey<E>>,
+    contributions: Contributions<E>,
     signature: Signature<E>,
 }
 
+impl<E: EngineBLS> Clone for BatchAssumingProofsOfPossession<E> {
+    fn clone(&self) -> BatchAssumingProofsOfPossession<E> {
+        BatchAssumingProofsOfPossession {
+            messages_n_publickeys: self.messages_n_publickeys.clone(),
+            contributions: self.contributions.clone(),
+            signature: self.signature,
+        }
+    }
+}
+
 impl<E: EngineBLS> BatchAssumingProofsOfPossession<E> {
     pub fn new() -> BatchAssumingProofsOfPossession<E> {
         BatchAssumingProofsOfPossession {
             messages_n_publickeys: HashMap::new(),
+            contributions: Contributions::new(),
             signature: Signature(E::SignatureGroup::zero()),
         }
     }
@@ -106,23 +228,65 @@ impl<E: EngineBLS> BatchAssumingProofsOfPossession<E> {
     ///
     /// Useful for constructing an aggregate signature, but we
     /// recommend instead using a custom types like `BitPoPSignedMessage`.
-    pub fn add_message_n_publickey(&mut self, message: &Message, publickey: &PublicKey<E>) {
+    ///
+    /// We refuse the identity public key, leaving `self` unchanged.
+    pub fn add_message_n_publickey(&mut self, message: &Message, publickey: &PublicKey<E>)
+     -> Result<(),IdentityPublicKey>
+    {
+        if publickey.0.is_zero() {
+            return Err(IdentityPublicKey);
+        }
         self.messages_n_publickeys.entry(*message)
             .and_modify(|pk0| pk0.0.add_assign(&publickey.0) )
             .or_insert(*publickey);
+        self.contributions.add(message,publickey);
+        Ok(())
+    }
+
+    /// Remove a BLS signature previously aggregated from a singleton
+    ///
+    /// We subtract both the signature and the signer's public key.
+    /// We track which public keys contributed to each message, so we
+    /// return an error, leaving `self` unchanged, if this signer never
+    /// contributed this message.  We cannot detect if the signature
+    /// differs from the one previously added, but then verification fails.
+    pub fn remove(&mut self, signed: &SignedMessage<E>) -> Result<(),UnknownContribution> {
+        if self.contributions.remove(&signed.message,&signed.publickey) ? {
+            self.messages_n_publickeys.remove(&signed.message);
+        } else {
+            self.messages_n_publickeys.get_mut(&signed.message)
+                .expect("Contributions track messages_n_publickeys")
+                .0.sub_assign(&signed.publickey.0);
+        }
+        self.signature.0.sub_assign(&signed.signature.0);
+        Ok(())
     }
 
     /// Aggregage BLS signatures assuming they have proofs-of-possession
-    pub fn aggregate<'a,S>(&mut self, signed: &'a S) 
+    ///
+    /// We refuse `signed` entirely if it contains the identity public
+    /// key, leaving `self` unchanged.
+    pub fn aggregate<'a,S>(&mut self, signed: &'a S) -> Result<(),IdentityPublicKey>
     where
         &'a S: Signed<E=E>,
         <&'a S as Signed>::PKG: Borrow<PublicKey<E>>,
     {
         let signature = signed.signature();
-        for (message,pubickey) in signed.messages_and_publickeys() {
-            self.add_message_n_publickey(message.borrow(),pubickey.borrow());
+        let messages_n_publickeys = signed.messages_and_publickeys()
+            .map(|(m,pk)| {
+                let (m, pk): (&Message, &PublicKey<E>) = (m.borrow(), pk.borrow());
+                (*m, *pk)
+            })
+            .collect::<Vec<(Message,PublicKey<E>)>>();
+        if messages_n_publickeys.iter().any(|(_,pk)| pk.0.is_zero()) {
+            return Err(IdentityPublicKey);
+        }
+        for (message,pubickey) in messages_n_publickeys.iter() {
+            self.add_message_n_publickey(message,pubickey)
+                .expect("We checked for the identity above");
         }
         self.add_signature(&signature);
+        Ok(())
     }
 }
 
@@ -152,5 +316,267 @@ impl<'a,E: EngineBLS> Signed for &'a BatchAssumingProofsOfPossession<E> {
 }
 
 
+/// Error type for `AggregatedBySameMessage::add`
+///
+/// We leave the aggregate signature unchanged when returning this.
+#[derive(Debug)]
+pub enum SameMessageError {
+    /// Attempted to aggregate a signature on a different message.
+    MismatchedMessage,
+    /// Attempted to aggregate a contribution by the identity public key,
+    /// which verifies on any message without any secret key.
+    IdentityPublicKey,
+}
+
+impl ::std::fmt::Display for SameMessageError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        use self::SameMessageError::*;
+        match self {
+            MismatchedMessage => write!(f, "Cannot aggregate signatures on different messages."),
+            IdentityPublicKey => write!(f, "Attempted to aggregate the identity public key."),
+        }
+    }
+}
+
+impl ::std::error::Error for SameMessageError {
+    fn description(&self) -> &str {
+        use self::SameMessageError::*;
+        match self {
+            MismatchedMessage => "Cannot aggregate signatures on different messages.",
+            IdentityPublicKey => "Attempted to aggregate the identity public key.",
+        }
+    }
+}
+
+/// Aggregate BLS signature by many signers on one message, for whom
+/// we previously checked proofs-of-possession.
+///
+/// We sum both the public keys and signatures as we aggregate, so
+/// verification costs one pairing for the summed public key, plus one
+/// for the signature, regardless of the number of signers.  Consensus
+/// rounds in which all signers sign the same message benefit most.
+///
+/// It's insecure to use this aggregation strategy without first
+/// checking proofs-of-possession for all signers, exactly like with
+/// `BatchAssumingProofsOfPossession`.  We cannot detect duplicate
+/// signers either, so prefer `BitSignedMessage` when signers come
+/// from a known table.
+// #[derive(Clone)]
+pub struct AggregatedBySameMessage<E: EngineBLS> {
+    message: Message,
+    publickey: PublicKey<E>,
+    signature: Signature<E>,
+}
+
+impl<E: EngineBLS> Clone for AggregatedBySameMessage<E> {
+    fn clone(&self) -> AggregatedBySameMessage<E> {
+        AggregatedBySameMessage {
+            message: self.message,
+            publickey: self.publickey,
+            signature: self.signature,
+        }
+    }
+}
+
+impl<E: EngineBLS> AggregatedBySameMessage<E> {
+    pub fn new(message: Message) -> AggregatedBySameMessage<E> {
+        AggregatedBySameMessage {
+            message,
+            publickey: PublicKey(E::PublicKeyGroup::zero()),
+            signature: Signature(E::SignatureGroup::zero()),
+        }
+    }
+
+    /// Aggregate one signed message, after checking that it signs
+    /// our message by a public key other than the identity, and
+    /// leaving `self` unchanged otherwise.
+    ///
+    /// Callers must have verified the signer's proof-of-possession,
+    /// because we cannot detect rogue key attacks here.
+    pub fn add(&mut self, signed: &SignedMessage<E>) -> Result<(),SameMessageError> {
+        if signed.message != self.message {
+            return Err(SameMessageError::MismatchedMessage);
+        }
+        if signed.publickey.0.is_zero() {
+            return Err(SameMessageError::IdentityPublicKey);
+        }
+        self.publickey.0.add_assign(&signed.publickey.0);
+        self.signature.0.add_assign(&signed.signature.0);
+        Ok(())
+    }
+
+    /// Return the message all our signers signed.
+    pub fn message(&self) -> Message { self.message }
+
+    /// Return the sum of our signers' public keys.
+    pub fn publickey(&self) -> PublicKey<E> { self.publickey }
+}
+
+impl<E: EngineBLS> Signed for &AggregatedBySameMessage<E> {
+    type E = E;
+
+    type M = Message;
+    type PKG = PublicKey<E>;
+    type PKnM = ::std::iter::Once<(Message, PublicKey<E>)>;
+
+    fn messages_and_publickeys(self) -> Self::PKnM {
+        once((self.message, self.publickey))
+    }
+
+    fn signature(&self) -> Signature<E> { self.signature }
+
+    fn verify(self) -> bool {
+        // We have only one message and public key, so nothing remains
+        // to optimize.
+        verify_simple(self)
+    }
+}
 
 
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng};  // Rng
+
+    use super::*;
+    use super::super::distinct::DistinctMessages;
+
+    #[test]
+    fn rogue_key_attack() {
+        let context = Context::new(b"ctx");
+        let message = Message::new(&context,b"rogue key attack");
+
+        let honest = Keypair::<ZBLS>::generate(thread_rng());
+        let honest_proof = honest.secret.prove_possession();
+        assert!( honest.public.verify_possession(&honest_proof) );
+        assert!( honest_proof == honest.into_vartime().secret.prove_possession() );
+
+        // The adversary chooses their rogue key to be `a g1 - honest`,
+        // so the aggregate public key becomes `a g1`.
+        let attacker = SecretKeyVT::<ZBLS>::generate(thread_rng());
+        let mut rogue = attacker.into_public();
+        rogue.0.sub_assign(&honest.public.0);
+        let forged = attacker.sign(&context,b"rogue key attack");
+
+        // Skipping proof-of-possession checks accepts a forged
+        // aggregate signature by both the honest and rogue keys.
+        let mut batch = BatchAssumingProofsOfPossession::<PoP<ZBLS>>::new();
+        batch.add_message_n_publickey(&message, &honest.public.i_have_checked_this_proof_of_possession()).unwrap();
+        batch.add_message_n_publickey(&message, &rogue.i_have_checked_this_proof_of_possession()).unwrap();
+        batch.add_signature(&Signature(forged.0));
+        assert!( batch.verify() );
+        assert!( verifiers::verify_simple(&batch) );
+
+        // Yet the adversary cannot produce a proof-of-possession for the
+        // rogue key, so checking them prevents the forgery.
+        assert!( ! rogue.verify_possession(&attacker.prove_possession()) );
+        assert!( ! rogue.verify_possession(&ProofOfPossession(forged.0)) );
+        assert!( ! honest.public.verify_possession(&attacker.prove_possession()) );
+
+        // Proofs-of-possession are not signatures on any message.
+        let replayed = Signature::<ZBLS>(honest_proof.0);
+        let bytes = honest.public.0.into_affine().into_uncompressed();
+        assert!( ! honest.public.verify(&Context::new(PROOF_OF_POSSESSION_CONTEXT), bytes.as_ref(), &replayed) );
+        assert!( ! honest.public.verify(&Context::new(b""), bytes.as_ref(), &replayed) );
+    }
+
+    #[test]
+    fn remove_signer() {
+        let context = Context::new(b"ctx");
+        let msgs: [&[u8]; 3] = [ b"Message1", b"Message1", b"Message2" ];
+        let mut keypairs = (0..3).map(|_| Keypair::<ZBLS>::generate(thread_rng())).collect::<Vec<_>>();
+        let sigs = msgs.iter().zip(keypairs.iter_mut()).map(|(m,k)| k.sign(&context,m)).collect::<Vec<_>>();
+
+        let mut batch = BatchAssumingProofsOfPossession::<ZBLS>::new();
+        for sig in sigs.iter() { batch.aggregate(sig).unwrap(); }
+        let mut expected = BatchAssumingProofsOfPossession::<ZBLS>::new();
+        expected.aggregate(&sigs[0]).unwrap();
+        expected.aggregate(&sigs[2]).unwrap();
+
+        // A registered signer who never signed Message1 contributed nothing there.
+        let stranger = keypairs[2].sign(&context,msgs[0]);
+        let before = batch.clone();
+        assert!( batch.remove(&stranger).is_err() );
+        assert!( batch.signature == before.signature );
+        assert!( batch.messages_n_publickeys == before.messages_n_publickeys );
+        assert!( batch.verify() );
+
+        assert!( batch.remove(&sigs[1]).is_ok() );
+        assert!( batch.signature == expected.signature );
+        assert!( batch.messages_n_publickeys == expected.messages_n_publickeys );
+        assert!( batch.verify() );
+
+        assert!( batch.remove(&sigs[2]).is_ok() );
+        assert!( batch.messages_n_publickeys.len() == 1 );
+        assert!( batch.verify() );
+        assert!( batch.remove(&sigs[2]).is_err() );
+        assert!( batch.remove(&sigs[1]).is_err() );
+        assert!( batch.verify() );
+    }
+
+    #[test]
+    fn same_message() {
+        let context = Context::new(b"ctx");
+        let message = Message::new(&context,b"consensus round");
+        let mut keypairs = (0..4).map(|_| Keypair::<ZBLS>::generate(thread_rng())).collect::<Vec<_>>();
+        let sigs = keypairs.iter_mut().map(|k| k.sign(&context,b"consensus round")).collect::<Vec<_>>();
+
+        // One signer agrees with distinct message aggregation.
+        let mut agg = AggregatedBySameMessage::<ZBLS>::new(message);
+        assert!( agg.add(&sigs[0]).is_ok() );
+        let dm = DistinctMessages::<ZBLS>::new().add(&sigs[0]).unwrap();
+        assert!( agg.verify() && dm.verify() );
+        assert!( (&agg).signature() == (&dm).signature() );
+        assert!( agg.messages_and_publickeys().collect::<Vec<_>>() == vec![(message, sigs[0].publickey)] );
+
+        for sig in sigs[1..].iter() {
+            assert!( agg.add(sig).is_ok() );
+        }
+        assert!( agg.messages_and_publickeys().count() == 1 );
+        assert!( agg.verify() );
+        assert!( verify_with_distinct_messages(&agg,true) );
+
+        let other = keypairs[0].sign(&context,b"another round");
+        let before = agg.clone();
+        match agg.add(&other) {
+            Err(SameMessageError::MismatchedMessage) => {},
+            _ => panic!("Signature on another message was aggregated"),
+        }
+        assert!( (&agg).signature() == (&before).signature() && agg.publickey() == before.publickey() );
+
+        let mut bad = AggregatedBySameMessage::<ZBLS>::new(message);
+        let mut forged = sigs[1].clone();
+        forged.signature = sigs[2].signature;
+        assert!( bad.add(&sigs[0]).is_ok() && bad.add(&forged).is_ok() );
+        assert!( ! bad.verify() );
+    }
+
+    #[test]
+    fn identity_publickey() {
+        let identity = PublicKey::<ZBLS>(<ZBLS as EngineBLS>::PublicKeyGroup::zero());
+        let zero = ProofOfPossession::<ZBLS>(<ZBLS as EngineBLS>::SignatureGroup::zero());
+        assert!( ! identity.verify_possession(&zero) );
+
+        let context = Context::new(b"ctx");
+        let message = Message::new(&context,b"never signed");
+        let signed = Keypair::<ZBLS>::generate(thread_rng()).sign(&context,b"never signed");
+        let mut batch = BatchAssumingProofsOfPossession::<ZBLS>::new();
+        assert!( batch.aggregate(&signed).is_ok() );
+        assert!( batch.add_message_n_publickey(&Message::new(&context,b"other"), &identity).is_err() );
+
+        let forged = SignedMessage { message, publickey: identity, signature: Signature(zero.0) };
+        assert!( forged.verify() );
+        assert!( batch.aggregate(&forged).is_err() );
+        assert!( batch.messages_and_publickeys().len() == 1 );
+        assert!( (&batch).signature() == signed.signature );
+        assert!( batch.verify() );
+
+        let mut agg = AggregatedBySameMessage::<ZBLS>::new(message);
+        assert!( agg.add(&signed).is_ok() );
+        match agg.add(&forged) {
+            Err(SameMessageError::IdentityPublicKey) => {},
+            _ => panic!("Identity public key was aggregated"),
+        }
+        assert!( agg.publickey() == signed.publickey && (&agg).signature() == signed.signature );
+        assert!( agg.verify() );
+    }
+}
@@ -29,12 +29,15 @@ use rand::{Rng, thread_rng, SeedableRng, chacha::ChaChaRng};
 // use rand::prelude::*; // ThreadRng,thread_rng
 // use rand_chacha::ChaChaRng;
 use sha3::{Shake128, digest::{Input,ExtendableOutput,XofReader}};
+use sha2::{Digest, Sha256};
+use hkdf::Hkdf;
 
 // use std::borrow::{Borrow,BorrowMut};
 use std::iter::once;
 use std::io;
 
 use super::*;
+use super::codec::PointCodec;
 
 
 // //////////////// SECRETS //////////////// //
@@ -47,7 +50,176 @@ impl<E: EngineBLS> Clone for SecretKeyVT<E> {
     fn clone(&self) -> Self { SecretKeyVT(self.0) }
 }
 
-// TODO: Serialization
+/// Overwrite the backing storage of a secret value with zeros.
+///
+/// `PrimeField` exposes no mutable access to its limbs, so we zero
+/// the raw bytes instead, using volatile writes from `zeroize` that
+/// the optimizer cannot elide.  All our secrets are scalars or curve
+/// points, for which all zero bytes represent zero or the identity.
+#[cfg(feature = "zeroize")]
+fn zeroize_in_place<T: Copy>(secret: &mut T) {
+    use zeroize::Zeroize;
+    let bytes = unsafe {
+        ::std::slice::from_raw_parts_mut(secret as *mut T as *mut u8, ::std::mem::size_of::<T>())
+    };
+    bytes.zeroize();
+}
+
+#[cfg(feature = "zeroize")]
+impl<E: EngineBLS> Drop for SecretKeyVT<E> {
+    fn drop(&mut self) {
+        zeroize_in_place(&mut self.0);
+    }
+}
+
+/// Error type for deriving a secret key from a seed shorter than
+/// the 32 bytes EIP-2333 requires.
+#[derive(Debug)]
+pub struct ShortSeed;
+
+impl ::std::fmt::Display for ShortSeed {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        write!(f, "EIP-2333 requires seeds of at least 32 bytes.")
+    }
+}
+
+impl ::std::error::Error for ShortSeed {
+    fn description(&self) -> &str {
+        "EIP-2333 requires seeds of at least 32 bytes."
+    }
+}
+
+/// EIP-2333 `HKDF_mod_r`, which derives a nonzero BLS12-381 scalar
+/// from input key material.
+///
+/// We reduce 48 bytes of HKDF output modulo `r`, so the result has
+/// negligible bias, and rehash the salt to retry if we obtain zero.
+fn hkdf_mod_r(ikm: &[u8]) -> ::pairing::bls12_381::Fr {
+    use pairing::bls12_381::{Fr,FrRepr};
+
+    let mut ikm = ikm.to_vec();
+    ikm.push(0);
+    let byte = Fr::from_repr(FrRepr::from(256)).unwrap();
+    let mut salt = Sha256::digest(b"BLS-SIG-KEYGEN-SALT-");
+    loop {
+        let mut okm = [0u8; 48];
+        Hkdf::<Sha256>::new(Some(&salt[..]), &ikm)
+            .expand(&[0, 48], &mut okm)
+            .expect("48 bytes is a valid HKDF-SHA256 output length");
+        // OS2IP(okm) mod r via Horner's rule, since okm is big endian.
+        let mut sk = Fr::zero();
+        for b in okm.iter() {
+            sk.mul_assign(&byte);
+            sk.add_assign(&Fr::from_repr(FrRepr::from(*b as u64)).unwrap());
+        }
+        if ! sk.is_zero() { return sk; }
+        salt = Sha256::digest(&salt[..]);
+    }
+}
+
+/// EIP-2333 `parent_SK_to_lamport_PK`, which compresses a Lamport
+/// public key derived from the parent secret key and child index.
+fn parent_to_lamport_publickey(parent: &[u8; 32], index: u32) -> [u8; 32] {
+    let mut lamport_publickey = Sha256::new();
+    let not_parent = {
+        let mut not_parent = *parent;
+        for b in not_parent.iter_mut() { *b = !*b; }
+        not_parent
+    };
+    for ikm in [*parent, not_parent].iter() {
+        // EIP-2333 `IKM_to_lamport_SK` yields 255 chunks of 32 bytes.
+        let mut lamport_secret = vec![0u8; 255*32];
+        Hkdf::<Sha256>::new(Some(&index.to_be_bytes()[..]), ikm)
+            .expand(&[], &mut lamport_secret)
+            .expect("8160 bytes is a valid HKDF-SHA256 output length");
+        for chunk in lamport_secret.chunks(32) {
+            // Digest::input disambiguates from sha3's Input trait.
+            Digest::input(&mut lamport_publickey, Sha256::digest(chunk));
+        }
+    }
+    let mut compressed = [0u8; 32];
+    compressed.copy_from_slice(&Digest::result(lamport_publickey));
+    compressed
+}
+
+impl<E> SecretKeyVT<E> where E: UnmutatedKeys<Scalar = ::pairing::bls12_381::Fr> {
+    /// Derive a master secret key from a seed, following EIP-2333.
+    ///
+    /// We produce the same key for the same seed, regardless of
+    /// orientation, so seeds backed up as mnemonics regenerate keys.
+    /// We never produce the zero scalar, even from an all zero seed.
+    ///
+    /// We return an error if `seed` is shorter than 32 bytes,
+    /// as EIP-2333 requires.
+    pub fn from_seed(seed: &[u8]) -> Result<Self,ShortSeed> {
+        if seed.len() < 32 { return Err(ShortSeed); }
+        Ok(SecretKeyVT(hkdf_mod_r(seed)))
+    }
+
+    /// Derive the child secret key with the given index, following
+    /// EIP-2333, so `from_seed` and `derive_child` walk the tree.
+    pub fn derive_child(&self, index: u32) -> Self {
+        let mut parent = self.to_bytes();
+        parent.reverse();  // EIP-2333 serializes scalars big endian
+        SecretKeyVT(hkdf_mod_r(&parent_to_lamport_publickey(&parent,index)))
+    }
+
+    /// Serialize our secret key as 32 little endian bytes.
+    ///
+    /// We support only BLS12-381 here because other curves have
+    /// scalars of different sizes, but both orientations share
+    /// the same scalar field.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        self.write(&mut bytes[..]).expect("BLS12-381 scalars serialize into 32 bytes");
+        bytes
+    }
+
+    /// Deserialize our secret key from 32 little endian bytes,
+    /// failing if they do not represent a canonical nonzero scalar.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self,PrimeFieldDecodingError> {
+        let repr = Self::read(&bytes[..]).expect("BLS12-381 scalars deserialize from 32 bytes");
+        Self::from_repr(repr)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<E> ::serde::Serialize for SecretKeyVT<E> where E: UnmutatedKeys {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: ::serde::Serializer {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes).expect("Writing into a Vec cannot fail");
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'d,E> ::serde::Deserialize<'d> for SecretKeyVT<E> where E: UnmutatedKeys {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: ::serde::Deserializer<'d> {
+        use std::fmt;
+        use std::marker::PhantomData;
+
+        struct MyVisitor<EE: UnmutatedKeys>(PhantomData<EE>);
+
+        impl<'d,EE: UnmutatedKeys> ::serde::de::Visitor<'d> for MyVisitor<EE> {
+            type Value = SecretKeyVT<EE>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("A BLS secret key")
+            }
+
+            fn visit_bytes<ERR>(self, bytes: &[u8]) -> Result<SecretKeyVT<EE>, ERR> where ERR: ::serde::de::Error {
+                let mut repr = <EE::Scalar as PrimeField>::Repr::default();
+                if bytes.len() != 8 * repr.as_ref().len() {
+                    return Err(ERR::invalid_length(bytes.len(), &self));
+                }
+                repr.read_le(bytes).map_err(|_| ERR::custom("Secret key decoding error")) ?;
+                SecretKeyVT::<EE>::from_repr(repr)
+                .map_err(|_| ERR::custom("Secret key not a canonical scalar"))
+            }
+        }
+        deserializer.deserialize_bytes(MyVisitor(PhantomData))
+    }
+}
 
 impl<E: EngineBLS> SecretKeyVT<E> where E: UnmutatedKeys {
     /// Convert our secret key to its representation type, which
@@ -65,8 +237,14 @@ impl<E: EngineBLS> SecretKeyVT<E> where E: UnmutatedKeys {
     /// satisfies `Default`, `AsMut<[u64]>`, and `ff::PrimeFieldRepr`.
     /// We suggest `ff::PrimeFieldRepr::read_le` for deserialization,
     /// invoked via our `read` method, which requires a seperate call.
+    ///
+    /// We reject zero, whose public key would be the identity.
     pub fn from_repr(repr: <E::Scalar as PrimeField>::Repr) -> Result<Self,PrimeFieldDecodingError> {
-        Ok(SecretKeyVT(<E::Scalar as PrimeField>::from_repr(repr) ?))
+        let secret = <E::Scalar as PrimeField>::from_repr(repr) ?;
+        if secret.is_zero() {
+            return Err(PrimeFieldDecodingError::NotInField("zero secret key".into()));
+        }
+        Ok(SecretKeyVT(secret))
     }
     pub fn read<R: io::Read>(reader: R) -> io::Result<<E::Scalar as PrimeField>::Repr> {
         let mut repr = <E::Scalar as PrimeField>::Repr::default();
@@ -81,9 +259,18 @@ impl<E: EngineBLS> SecretKeyVT<E> where E: UnmutatedKeys {
 }
 
 impl<E: EngineBLS> SecretKeyVT<E> {
-    /// Sign without side channel protections from key mutation.
-    pub fn sign(&self, message: Message) -> Signature<E> {
-        let mut s = message.hash_to_signature_curve::<E>();
+    /// Sign `message` under `context` without side channel protections
+    /// from key mutation.
+    pub fn sign(&self, context: &Context, message: &[u8]) -> Signature<E> {
+        self.sign_message(Message::new(context,message))
+    }
+
+    fn sign_message(&self, message: Message) -> Signature<E> {
+        self.sign_point(message.hash_to_signature_curve::<E>())
+    }
+
+    /// Sign some already hashed point on the signature curve.
+    pub(crate) fn sign_point(&self, mut s: E::SignatureGroup) -> Signature<E> {
         s.mul_assign(self.0);
         // s.normalize();   // VRFs are faster if we only normalize once, but no normalize method exists.
         // E::SignatureGroup::batch_normalization(&mut [&mut s]);  
@@ -118,6 +305,58 @@ impl<E: EngineBLS> SecretKeyVT<E> {
     }
 }
 
+/// Swap `a` and `b` if `swap` is one, and leave them if zero,
+/// without branching on `swap`.
+///
+/// Again `CurveProjective` exposes no coordinates, so we mask the
+/// raw bytes, which works since curve points contain no pointers.
+fn conditional_swap<T: Copy>(a: &mut T, b: &mut T, swap: u8) {
+    let mask = 0u8.wrapping_sub(swap);
+    let size = ::std::mem::size_of::<T>();
+    let (a,b) = unsafe { (
+        ::std::slice::from_raw_parts_mut(a as *mut T as *mut u8, size),
+        ::std::slice::from_raw_parts_mut(b as *mut T as *mut u8, size),
+    ) };
+    for (x,y) in a.iter_mut().zip(b.iter_mut()) {
+        let t = mask & (*x ^ *y);
+        *x ^= t;
+        *y ^= t;
+    }
+}
+
+/// Multiply `point` by a secret `scalar` using a Montgomery ladder.
+///
+/// `CurveProjective::mul_assign` uses wNAF, whose table lookups and
+/// additions depend upon the scalar's digits, and skips leading zeros.
+/// We instead perform one addition and one doubling for each of
+/// `NUM_BITS` bits, selecting operands with `conditional_swap`.
+///
+/// Also, pairing's projective addition formulas shortcut the identity,
+/// so we offset both ladder registers by the generator `Q`, computing
+/// `2^NUM_BITS Q + scalar * point` and then subtracting `2^NUM_BITS Q`,
+/// so neither register becomes the identity except with negligible
+/// probability, even during leading zero bits.
+fn mul_ct<G, S>(point: &G, scalar: S) -> G
+where G: CurveProjective, S: Into<<G::Scalar as PrimeField>::Repr>
+{
+    let repr = scalar.into();
+    let limbs = repr.as_ref();
+    let mut offset = G::one();
+    let mut r0 = offset;
+    let mut r1 = offset;
+    r1.add_assign(point);
+    for i in (0..<G::Scalar as PrimeField>::NUM_BITS as usize).rev() {
+        let bit = ((limbs[i / 64] >> (i % 64)) & 1) as u8;
+        conditional_swap(&mut r0, &mut r1, bit);
+        r1.add_assign(&r0);
+        r0.double();
+        conditional_swap(&mut r0, &mut r1, bit);
+        offset.double();
+    }
+    r0.sub_assign(&offset);
+    r0
+}
+
 /// Secret signing key that is split to provide side channel protection.
 ///
 /// A simple key splitting works because
@@ -138,24 +377,38 @@ impl<E: EngineBLS> SecretKeyVT<E> {
 /// ```rust,no_run
 /// # extern crate bls_like as bls;
 /// # extern crate rand;
-/// # use bls::{SecretKey,ZBLS,Message};
+/// # use bls::{SecretKey,ZBLS,Context};
 /// # use rand::thread_rng;
-/// # let message = Message::new(b"ctx",b"test message");
+/// # let context = Context::new(b"ctx");
 /// let mut secret = ::std::cell::RefCell::new(SecretKey::<ZBLS>::generate(thread_rng()));
-/// let signature = secret.borrow_mut().sign(message,thread_rng());
+/// let signature = secret.borrow_mut().sign(&context,b"test message",thread_rng());
 /// ```
 /// If however `secret: Mutex<SecretKey>` or `secret: RwLock<SecretKey>`
 /// then one might avoid holding the write lock while signing, or even
 /// while sampling the random numbers by using other methods.
 ///
-/// Right now, we serialize using `SecretKey::into_vartime` and
-/// `SecretKeyVT::write`, so `secret.into_vartime().write(writer)?`.
-/// We deserialize using the `read`, `from_repr`, and `into_split`
-/// methods of `SecretKeyVT`, so roughly
+/// We serialize BLS12-381 secret keys with `to_bytes` and `from_bytes`,
+/// which resplit the key upon deserialization.  Otherwise, we serialize
+/// using `SecretKey::into_vartime` and `SecretKeyVT::write`, so
+/// `secret.into_vartime().write(writer)?`, and deserialize using the
+/// `read`, `from_repr`, and `into_split` methods of `SecretKeyVT`, so roughly
 /// `SecretKeyVT::from_repr(SecretKeyVT::read(reader) ?) ?.into_split(thread_rng())`.
 ///
-/// TODO: Provide sensible `to_bytes` and `from_bytes` methods
-/// for `ZBLS` and `TinyBLS<..>`.
+/// We multiply by our secret key halves using the constant time
+/// `mul_ct` in `sign_once`, `init_point_mutation`, and
+/// `prove_possession`.  Hashing the
+/// message to the curve handles only public data, and the curve
+/// point additions involve only masked points.  We remain variable
+/// time in `into_public`, whose calls should be rare, and throughout
+/// `SecretKeyVT`.  We caution that `mul_ct` only removes scalar
+/// dependent control flow at the group level, as pairing's field
+/// arithmetic branches on intermediate values in modular reductions,
+/// as does the scalar arithmetic in `resplit`.  Key splitting and
+/// point mutation exist to mitigate exactly these residual leaks.
+///
+/// With the `zeroize` feature, both `SecretKey` and `SecretKeyVT`
+/// overwrite their secrets with zeros when dropped.  We cannot zero
+/// copies made by moves though, so prefer boxing long lived keys.
 ///
 /// TODO: Is Pippenger’s algorithm, or another fast MSM algorithm,
 /// secure when used with key splitting?
@@ -175,14 +428,70 @@ impl<E: EngineBLS> Clone for SecretKey<E> {
     }
 }
 
-// TODO: Serialization
+/// We zero both key halves, and our mutated points too because
+/// `old_signed` is a known point multiplied by our secret key.
+#[cfg(feature = "zeroize")]
+impl<E: EngineBLS> Drop for SecretKey<E> {
+    fn drop(&mut self) {
+        zeroize_in_place(&mut self.key);
+        zeroize_in_place(&mut self.old_unsigned);
+        zeroize_in_place(&mut self.old_signed);
+    }
+}
+
+impl<E> SecretKey<E> where E: UnmutatedKeys<Scalar = ::pairing::bls12_381::Fr> {
+    /// Derive a master secret key from a seed, following EIP-2333,
+    /// and split it for side channel protection.
+    ///
+    /// We return an error if `seed` is shorter than 32 bytes,
+    /// as EIP-2333 requires.
+    pub fn from_seed(seed: &[u8]) -> Result<Self,ShortSeed> {
+        Ok(SecretKeyVT::from_seed(seed) ?.into_split(thread_rng()))
+    }
+
+    /// Derive the child secret key with the given index, following
+    /// EIP-2333, and split it for side channel protection.
+    pub fn derive_child(&self, index: u32) -> Self {
+        self.into_vartime().derive_child(index).into_split(thread_rng())
+    }
+
+    /// Serialize our secret key as 32 little endian bytes.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.into_vartime().to_bytes()
+    }
+
+    /// Deserialize our secret key from 32 little endian bytes,
+    /// and split it using the default `ThreadRng`.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self,PrimeFieldDecodingError> {
+        Ok(SecretKeyVT::from_bytes(bytes) ?.into_split(thread_rng()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<E> ::serde::Serialize for SecretKey<E> where E: UnmutatedKeys {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: ::serde::Serializer {
+        ::serde::Serialize::serialize(&self.into_vartime(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'d,E> ::serde::Deserialize<'d> for SecretKey<E> where E: UnmutatedKeys {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: ::serde::Deserializer<'d> {
+        let secret = <SecretKeyVT<E> as ::serde::Deserialize<'d>>::deserialize(deserializer) ?;
+        Ok(secret.into_split(thread_rng()))
+    }
+}
 
 impl<E: EngineBLS> SecretKey<E> where E: UnmutatedKeys {
     /// Generate a secret key that is already split for side channel protection,
     /// but does not apply signed point mutation.
     pub fn generate_dirty<R: Rng>(mut rng: R) -> Self {
+        // We split a nonzero secret, since independent halves could sum to zero.
+        let mut key = [ E::generate(&mut rng), E::generate(&mut rng) ];
+        let mask = key[1];
+        key[0].sub_assign(&mask);
         SecretKey {
-            key: [ E::generate(&mut rng), E::generate(&mut rng) ],
+            key,
             old_unsigned: E::SignatureGroup::zero(),
             old_signed: E::SignatureGroup::zero(),
         }
@@ -205,9 +514,8 @@ impl<E: EngineBLS> SecretKey<E> {
     pub fn init_point_mutation<R: Rng>(&mut self, mut rng: R) {
         let mut s = rng.gen::<E::SignatureGroup>();
         self.old_unsigned = s;
-        self.old_signed = s;
-        self.old_signed.mul_assign(self.key[0]);
-        s.mul_assign(self.key[1]);
+        self.old_signed = mul_ct(&s, self.key[0]);
+        s = mul_ct(&s, self.key[1]);
         self.old_signed.add_assign(&s);
     }
 
@@ -238,13 +546,19 @@ impl<E: EngineBLS> SecretKey<E> {
     /// Avoid using directly without appropriate `replit` calls, but maybe
     /// useful in proof-of-concenpt code, as it does not require a mutable
     /// secret key.
-    pub fn sign_once(&mut self, message: Message) -> Signature<E> {
-        let mut z = message.hash_to_signature_curve::<E>();
+    pub fn sign_once(&mut self, context: &Context, message: &[u8]) -> Signature<E> {
+        self.sign_message_once(Message::new(context,message))
+    }
+
+    fn sign_message_once(&mut self, message: Message) -> Signature<E> {
+        self.sign_point_once(message.hash_to_signature_curve::<E>())
+    }
+
+    fn sign_point_once(&mut self, mut z: E::SignatureGroup) -> Signature<E> {
         z.sub_assign(&self.old_unsigned);
         self.old_unsigned = z.clone();
-        let mut t = z.clone();
-        t.mul_assign(self.key[0]);
-        z.mul_assign(self.key[1]);
+        let t = mul_ct(&z, self.key[0]);
+        z = mul_ct(&z, self.key[1]);
         z.add_assign(&t);
         let old_signed = self.old_signed.clone();
         self.old_signed = z.clone();
@@ -254,10 +568,29 @@ impl<E: EngineBLS> SecretKey<E> {
         Signature(z)
     }
 
-    /// Sign after respliting the secret key for side channel protections.
-    pub fn sign<R: Rng>(&mut self, message: Message, rng: R) -> Signature<E> {
+    /// Multiply `point` by our secret key halves separately, using
+    /// the constant time `mul_ct`, without mutating our key.
+    pub(crate) fn mul_point_ct(&self, point: &E::SignatureGroup) -> E::SignatureGroup {
+        let mut z = mul_ct(point, self.key[0]);
+        z.add_assign(&mul_ct(point, self.key[1]));
+        z
+    }
+
+    /// Sign `message` under `context` after respliting the secret key
+    /// for side channel protections.
+    pub fn sign<R: Rng>(&mut self, context: &Context, message: &[u8], rng: R) -> Signature<E> {
+        self.sign_message(Message::new(context,message),rng)
+    }
+
+    fn sign_message<R: Rng>(&mut self, message: Message, rng: R) -> Signature<E> {
+        self.sign_point(message.hash_to_signature_curve::<E>(),rng)
+    }
+
+    /// Sign some already hashed point on the signature curve, after
+    /// respliting the secret key for side channel protections.
+    pub(crate) fn sign_point<R: Rng>(&mut self, point: E::SignatureGroup, rng: R) -> Signature<E> {
         self.resplit(rng);
-        self.sign_once(message)
+        self.sign_point_once(point)
     }
 
     /// Derive our public key from our secret key
@@ -338,6 +671,35 @@ fn serde_error_from_group_decoding_error<ERR: ::serde::de::Error>(err: GroupDeco
 macro_rules! compression {
     ($wrapper:tt,$group:tt,$se:tt,$de:tt) => {
 
+impl<E: EngineBLS> $wrapper<E> {
+    /// Check that our signature or public key lies in the prime order
+    /// subgroup, not merely on the curve.
+    ///
+    /// We multiply by the group order, which works for whichever group
+    /// our orientation selects, despite G1 and G2 having different
+    /// cofactors.  Our `pairing` provides no faster endomorphism based
+    /// check.  We assume our point lies on the curve, which holds for
+    /// all points created by our constructors and decoders.
+    pub fn in_subgroup(&self) -> bool {
+        self.0.into_affine().mul(E::Scalar::char()).is_zero()
+    }
+
+    /// Reject the identity, which lies inside every subgroup, and thus
+    /// needs this separate check.
+    ///
+    /// An identity public key verifies the identity signature on any
+    /// message, so adversaries could claim signatures without any
+    /// secret key.  We return
+    /// `GroupDecodingError::UnexpectedInformation` here because
+    /// `pairing` provides no more specific error.
+    fn reject_identity(self) -> Result<Self,GroupDecodingError> {
+        if self.0.is_zero() {
+            return Err(GroupDecodingError::UnexpectedInformation);
+        }
+        Ok(self)
+    }
+}
+
 impl<E> $wrapper<E> where E: $se {
     /// Convert our signature or public key type to its compressed form.
     ///
@@ -347,6 +709,12 @@ impl<E> $wrapper<E> where E: $se {
     pub fn compress(&self) -> <<<E as EngineBLS>::$group as CurveProjective>::Affine as CurveAffine>::Compressed {
         self.0.into_affine().into_compressed()
     }
+
+    /// Encode our signature or public key type in compressed form,
+    /// following the serialization conventions of `C`.
+    pub fn encode<C: PointCodec>(&self) -> Vec<u8> {
+        C::encode_compressed(&self.0.into_affine())
+    }
 }
 
 impl<E> $wrapper<E> where E: $de {
@@ -356,8 +724,17 @@ impl<E> $wrapper<E> where E: $de {
     /// or `[u8; 96]` which satisfy `pairing::EncodedPoint` and permit
     /// creation and write access with `pairing::EncodedPoint::empty()`
     /// and `AsMef<[u8]>`, respectively.
+    ///
+    /// We reject points outside the prime order subgroup with
+    /// `GroupDecodingError::NotInSubgroup`, see `in_subgroup`, and
+    /// reject the identity too, see `reject_identity`.
     pub fn decompress(compressed: <<<E as EngineBLS>::$group as CurveProjective>::Affine as CurveAffine>::Compressed) -> Result<Self,GroupDecodingError> {
-        Ok($wrapper(compressed.into_affine()?.into_projective()))
+        // Decompression alone ensures our point lies on the curve.
+        let point = $wrapper(compressed.into_affine_unchecked()?.into_projective());
+        if ! point.in_subgroup() {
+            return Err(GroupDecodingError::NotInSubgroup);
+        }
+        point.reject_identity()
     }
 
     pub fn decompress_from_slice(slice: &[u8]) -> Result<Self,GroupDecodingError> {
@@ -369,6 +746,13 @@ impl<E> $wrapper<E> where E: $de {
         compressed.as_mut().copy_from_slice(slice);
         $wrapper::<E>::decompress(compressed)
     }
+
+    /// Decode our signature or public key type from compressed form,
+    /// following the serialization conventions of `C`.
+    pub fn decode<C: PointCodec>(bytes: &[u8]) -> Result<Self,GroupDecodingError> {
+        let point: <<E as EngineBLS>::$group as CurveProjective>::Affine = C::decode_compressed(bytes) ?;
+        $wrapper(point.into_projective()).reject_identity()
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -441,12 +825,21 @@ zbls_serialization!(Signature,TinyBLS,48);
 impl<E: EngineBLS> Signature<E> {
     const DESCRIPTION : &'static str = "A BLS signature";
 
-    /// Verify a single BLS signature
-    pub fn verify(&self, message: Message, publickey: &PublicKey<E>) -> bool {
+    /// Verify a single BLS signature on `message` under `context`
+    pub fn verify(&self, context: &Context, message: &[u8], publickey: &PublicKey<E>) -> bool {
+        self.verify_message(Message::new(context,message),publickey)
+    }
+
+    fn verify_message(&self, message: Message, publickey: &PublicKey<E>) -> bool {
+        self.verify_point(message.hash_to_signature_curve::<E>(),publickey)
+    }
+
+    /// Verify a single BLS signature on some already hashed point.
+    pub(crate) fn verify_point(&self, message: E::SignatureGroup, publickey: &PublicKey<E>) -> bool {
         let publickey = publickey.0.into_affine().prepare();
         // TODO: Bentchmark these two variants
         // Variant 1.  Do not batch any normalizations
-        let message = message.hash_to_signature_curve::<E>().into_affine().prepare();
+        let message = message.into_affine().prepare();
         let signature = self.0.into_affine().prepare();
         // Variant 2.  Batch signature curve normalizations
         //   let mut s = [E::hash_to_signature_curve(message), signature.0];
@@ -471,6 +864,32 @@ impl<E: EngineBLS> PublicKey<E> where E: DeserializePublicKey {
 }
 
 broken_derives!(PublicKey);
+
+/// We order public keys by their uncompressed encoding, which permits
+/// sorted allowlists, like in `verifiers::verify_with_sorted_allowlist`.
+/// Each comparison normalizes both points, so callers sorting many
+/// keys may prefer sorting by cached uncompressed encodings.
+impl<E: EngineBLS> Ord for PublicKey<E> {
+    fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+        let a = self.0.into_affine().into_uncompressed();
+        let b = other.0.into_affine().into_uncompressed();
+        a.as_ref().cmp(b.as_ref())
+    }
+}
+
+impl<E: EngineBLS> PartialOrd<Self> for PublicKey<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// We hash public keys by their uncompressed encoding too, which
+/// agrees with equality since equal points normalize identically.
+impl<E: EngineBLS> ::std::hash::Hash for PublicKey<E> {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        self.0.into_affine().into_uncompressed().as_ref().hash(state);
+    }
+}
 // borrow_wrapper!(PublicKey,PublicKeyGroup,0);
 compression!(PublicKey,PublicKeyGroup,UnmutatedKeys,DeserializePublicKey);
 zbls_serialization!(PublicKey,UsualBLS,48);
@@ -479,12 +898,38 @@ zbls_serialization!(PublicKey,TinyBLS,96);
 impl<E: EngineBLS> PublicKey<E> {
     const DESCRIPTION : &'static str = "A BLS signature";
 
-    pub fn verify(&self, message: Message, signature: &Signature<E>) -> bool {
-        signature.verify(message,self)
+    /// Verify a single BLS signature on `message` under `context`
+    pub fn verify(&self, context: &Context, message: &[u8], signature: &Signature<E>) -> bool {
+        signature.verify(context,message,self)
+    }
+
+    /// Prepare our public key for pairings, so that verifiers with
+    /// a fixed signer set may cache the result.
+    pub fn prepare(&self) -> PreparedPublicKey<E> {
+        PreparedPublicKey(self.0.into_affine().prepare())
     }
 }
 
 
+/// BLS public key prepared for pairings.
+///
+/// We cache these for fixed signer sets, like validators, and verify
+/// with `verifiers::verify_with_prepared_publickeys`, which avoids
+/// normalizing and preparing public keys on every verification.
+///
+/// We store the `Prepared` form of whichever curve our orientation
+/// places public keys upon, so the memory cost depends upon orientation.
+/// With `UsualBLS` on BLS12-381, public keys lie on G1, which prepares
+/// into merely its affine point, so roughly 100 bytes.  With `TinyBLS`,
+/// public keys lie on G2, which prepares into the line coefficients of
+/// the Miller loop, so 68 triples of `Fq2` elements, or about 19.5 KB.
+pub struct PreparedPublicKey<E: EngineBLS>(pub <<E::PublicKeyGroup as CurveProjective>::Affine as CurveAffine>::Prepared);
+
+impl<E: EngineBLS> Clone for PreparedPublicKey<E> {
+    fn clone(&self) -> Self { PreparedPublicKey(self.0.clone()) }
+}
+
+
 
 /// BLS Keypair
 ///
@@ -524,9 +969,10 @@ impl<E: EngineBLS> KeypairVT<E> {
         Keypair { secret, public }
     }
 
-    /// Sign a message creating a `SignedMessage` using a user supplied CSPRNG for the key splitting.
-    pub fn sign(&self, message: Message) -> SignedMessage<E> {
-        let signature = self.secret.sign(message);  
+    /// Sign `message` under `context` creating a `SignedMessage`.
+    pub fn sign(&self, context: &Context, message: &[u8]) -> SignedMessage<E> {
+        let message = Message::new(context,message);
+        let signature = self.secret.sign_message(message);  
         SignedMessage {
             message,
             publickey: self.public.clone(),
@@ -575,9 +1021,11 @@ impl<E: EngineBLS> Keypair<E> {
         KeypairVT { secret, public }
     }
 
-    /// Sign a message creating a `SignedMessage` using a user supplied CSPRNG for the key splitting.
-    pub fn sign_with_rng<R: Rng>(&mut self, message: Message, rng: R) -> SignedMessage<E> {
-        let signature = self.secret.sign(message,rng);
+    /// Sign `message` under `context` creating a `SignedMessage` using
+    /// a user supplied CSPRNG for the key splitting.
+    pub fn sign_with_rng<R: Rng>(&mut self, context: &Context, message: &[u8], rng: R) -> SignedMessage<E> {
+        let message = Message::new(context,message);
+        let signature = self.secret.sign_message(message,rng);
         SignedMessage {
             message,
             publickey: self.public,
@@ -585,9 +1033,10 @@ impl<E: EngineBLS> Keypair<E> {
         }
     }
 
-    /// Create a `SignedMessage` using the default `ThreadRng`.
-    pub fn sign(&mut self, message: Message) -> SignedMessage<E> {
-        self.sign_with_rng(message,thread_rng())
+    /// Sign `message` under `context` creating a `SignedMessage` using
+    /// the default `ThreadRng`.
+    pub fn sign(&mut self, context: &Context, message: &[u8]) -> SignedMessage<E> {
+        self.sign_with_rng(context,message,thread_rng())
     }
 }
 
@@ -595,12 +1044,22 @@ impl<E: EngineBLS> Keypair<E> {
 /// Message with attached BLS signature
 /// 
 /// 
-#[derive(Debug,Clone)]
+#[derive(Debug)]  // #[derive(Clone)] requires E: Clone
 pub struct SignedMessage<E: EngineBLS> {
     pub message: Message,
     pub publickey: PublicKey<E>,
     pub signature: Signature<E>,
 }
+
+impl<E: EngineBLS> Clone for SignedMessage<E> {
+    fn clone(&self) -> SignedMessage<E> {
+        SignedMessage {
+            message: self.message,
+            publickey: self.publickey,
+            signature: self.signature,
+        }
+    }
+}
 // TODO: Serialization
 
 // borrow_wrapper!(Signature,SignatureGroup,signature);
@@ -631,7 +1090,7 @@ impl<'a,E: EngineBLS> Signed for &'a SignedMessage<E> {
     fn signature(&self) -> Signature<E> { self.signature }
 
     fn verify(self) -> bool {
-        self.signature.verify(self.message, &self.publickey)
+        self.signature.verify_message(self.message, &self.publickey)
     }
 }
 
@@ -732,37 +1191,293 @@ mod tests {
         SignedMessage { message, publickey, signature }
     }
 
+    #[test]
+    fn secret_key_bytes() {
+        let keypair = Keypair::<ZBLS>::generate(thread_rng());
+        let secret = keypair.into_vartime().secret;
+        assert!( SecretKeyVT::<ZBLS>::from_bytes(&secret.to_bytes()).unwrap().0 == secret.0 );
+        assert!( SecretKey::<ZBLS>::from_bytes(&keypair.secret.to_bytes()).unwrap().into_public() == keypair.public );
+
+        let keypair = Keypair::<TBLS>::generate(thread_rng());
+        let secret = keypair.into_vartime().secret;
+        assert!( SecretKeyVT::<TBLS>::from_bytes(&secret.to_bytes()).unwrap().0 == secret.0 );
+        assert!( SecretKey::<TBLS>::from_bytes(&keypair.secret.to_bytes()).unwrap().into_public() == keypair.public );
+
+        // Exceeds the scalar field modulus
+        assert!( SecretKeyVT::<ZBLS>::from_bytes(&[0xffu8; 32]).is_err() );
+        assert!( SecretKey::<TBLS>::from_bytes(&[0xffu8; 32]).is_err() );
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_on_drop() {
+        use std::mem::ManuallyDrop;
+        use std::ptr::drop_in_place;
+
+        let context = Context::new(b"ctx");
+        let message = b"test message";
+        let mut secret = ManuallyDrop::new(SecretKeyVT::<ZBLS>::generate(thread_rng()));
+        let clone = (*secret).clone();
+        assert!( ! secret.0.is_zero() );
+        unsafe { drop_in_place(&mut *secret as *mut SecretKeyVT<ZBLS>); }
+        assert!( secret.0.is_zero() );
+        assert!( clone.into_public().verify(&context, message, &clone.sign(&context, message)) );
+
+        let mut secret = ManuallyDrop::new(SecretKey::<ZBLS>::generate(thread_rng()));
+        let mut clone = (*secret).clone();
+        unsafe { drop_in_place(&mut *secret as *mut SecretKey<ZBLS>); }
+        assert!( secret.key[0].is_zero() && secret.key[1].is_zero() );
+        assert!( secret.old_unsigned.is_zero() && secret.old_signed.is_zero() );
+        let publickey = clone.into_public();
+        assert!( publickey.verify(&context, message, &clone.sign(&context, message, thread_rng())) );
+
+        // Dropping normally must also be sound.
+        drop(clone);
+    }
+
+    fn mul_ct_agrees<G: CurveProjective>() {
+        let mut rng = thread_rng();
+        let mut scalars = (0..16).map(|_| rng.gen::<G::Scalar>()).collect::<Vec<_>>();
+        // Scalars with many leading zero bits, which wNAF skips
+        for x in [0u64, 1, 2, 3, 0xff, 1 << 63, rng.gen::<u32>() as u64].iter() {
+            let mut repr = <G::Scalar as PrimeField>::Repr::default();
+            repr.as_mut()[0] = *x;
+            scalars.push( G::Scalar::from_repr(repr).unwrap() );
+        }
+        let mut minus_one = G::Scalar::one();
+        minus_one.negate();
+        scalars.push(minus_one);
+
+        for scalar in scalars.iter() {
+            let point = rng.gen::<G>();
+            let mut expected = point;
+            expected.mul_assign(*scalar);
+            assert!( mul_ct(&point, *scalar) == expected );
+        }
+        assert!( mul_ct(&G::zero(), rng.gen::<G::Scalar>()).is_zero() );
+    }
+
+    #[test]
+    fn constant_time_multiplication() {
+        mul_ct_agrees::<<ZBLS as EngineBLS>::SignatureGroup>();
+        mul_ct_agrees::<<TBLS as EngineBLS>::SignatureGroup>();
+
+        let context = Context::new(b"ctx");
+        let mut secret = SecretKey::<ZBLS>::generate(thread_rng());
+        let vartime = secret.into_vartime();
+        assert!( secret.sign(&context,b"test message",thread_rng()) == vartime.sign(&context,b"test message") );
+    }
+
+    #[test]
+    fn eip2333_test_vectors() {
+        use pairing::bls12_381::Fr;
+
+        let vectors: [(&str,&str,u32,&str); 2] = [
+            ("c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+             "6083874454709270928345386274498605044986640685124978867557563392430687146096",
+             0,
+             "20397789859736650942317412262472558107875392172444076792671091975210932703118"),
+            ("3141592653589793238462643383279502884197169399375105820974944592",
+             "29757020647961307431480504535336562678282505419141012933316116377660817309383",
+             3141592653,
+             "25457201688850691947727629385191704516744796114925897962676248250929345014287"),
+        ];
+        for (seed,master,index,child) in vectors.iter() {
+            let seed = (0..seed.len()/2)
+                .map(|i| u8::from_str_radix(&seed[2*i..2*i+2],16).unwrap())
+                .collect::<Vec<u8>>();
+            let master_secret = SecretKeyVT::<ZBLS>::from_seed(&seed).unwrap();
+            assert!( master_secret.0 == Fr::from_str(master).unwrap() );
+            assert!( master_secret.derive_child(*index).0 == Fr::from_str(child).unwrap() );
+
+            // Orientation independent and compatible with key splitting
+            assert!( SecretKeyVT::<TBLS>::from_seed(&seed).unwrap().0 == master_secret.0 );
+            let split = SecretKey::<ZBLS>::from_seed(&seed).unwrap();
+            assert!( split.into_vartime().0 == master_secret.0 );
+            assert!( split.derive_child(*index).into_vartime().0 == Fr::from_str(child).unwrap() );
+        }
+
+        assert!( ! SecretKeyVT::<ZBLS>::from_seed(&[0u8; 32]).unwrap().0.is_zero() );
+        assert!( SecretKeyVT::<ZBLS>::from_seed(&[0u8; 31]).is_err() );
+        assert!( SecretKey::<ZBLS>::from_seed(&[]).is_err() );
+    }
+
+    /// Decompress a point on the curve but outside the prime order
+    /// subgroup, by searching small `x` coordinates.
+    fn outside_subgroup<G: CurveAffine>() -> G {
+        for x in 1u8..=255 {
+            let mut compressed = G::Compressed::empty();
+            {
+                let bytes = compressed.as_mut();
+                let l = bytes.len();
+                bytes[0] = 0x80;  // compression flag
+                bytes[l-1] = x;
+            }
+            if compressed.into_affine().is_ok() { continue; }
+            if let Ok(point) = compressed.into_affine_unchecked() { return point; }
+        }
+        unreachable!("Some small x coordinate lies on the curve outside the subgroup");
+    }
+
+    #[test]
+    fn subgroup_checks() {
+        use pairing::bls12_381::{Fr,G1Affine,G2Affine};
+
+        let g1 = outside_subgroup::<G1Affine>();
+        let g2 = outside_subgroup::<G2Affine>();
+        // Points in the small torsion subgroups fail as well.
+        let g1_torsion = g1.mul(Fr::char());
+        let g2_torsion = g2.mul(Fr::char());
+        assert!( ! g1_torsion.is_zero() && ! g2_torsion.is_zero() );
+        for p in [g1.into_projective(), g1_torsion].iter() {
+            assert!( ! PublicKey::<ZBLS>(*p).in_subgroup() );
+            assert!( ! Signature::<TBLS>(*p).in_subgroup() );
+        }
+        for p in [g2.into_projective(), g2_torsion].iter() {
+            assert!( ! Signature::<ZBLS>(*p).in_subgroup() );
+            assert!( ! PublicKey::<TBLS>(*p).in_subgroup() );
+        }
+
+        let mut g1_bytes = [0u8; 48];
+        g1_bytes.copy_from_slice(g1.into_compressed().as_ref());
+        let mut g2_bytes = [0u8; 96];
+        g2_bytes.copy_from_slice(g2.into_compressed().as_ref());
+        match PublicKey::<ZBLS>::from_bytes(&g1_bytes) {
+            Err(GroupDecodingError::NotInSubgroup) => {},
+            _ => panic!("UsualBLS public key outside subgroup accepted"),
+        }
+        match Signature::<ZBLS>::from_bytes(&g2_bytes) {
+            Err(GroupDecodingError::NotInSubgroup) => {},
+            _ => panic!("UsualBLS signature outside subgroup accepted"),
+        }
+        match PublicKey::<TBLS>::from_bytes(&g2_bytes) {
+            Err(GroupDecodingError::NotInSubgroup) => {},
+            _ => panic!("TinyBLS public key outside subgroup accepted"),
+        }
+        match Signature::<TBLS>::from_bytes(&g1_bytes) {
+            Err(GroupDecodingError::NotInSubgroup) => {},
+            _ => panic!("TinyBLS signature outside subgroup accepted"),
+        }
+
+        let mut keypair = Keypair::<ZBLS>::generate(thread_rng());
+        let signed = keypair.sign(&Context::new(b"ctx"),b"test message");
+        assert!( signed.publickey.in_subgroup() && signed.signature.in_subgroup() );
+        let mut keypair = Keypair::<TBLS>::generate(thread_rng());
+        let signed = keypair.sign(&Context::new(b"ctx"),b"test message");
+        assert!( signed.publickey.in_subgroup() && signed.signature.in_subgroup() );
+    }
+
+    #[test]
+    fn identity_rejected() {
+        let g1_identity = PublicKey::<ZBLS>(<ZBLS as EngineBLS>::PublicKeyGroup::zero());
+        let g2_identity = Signature::<ZBLS>(<ZBLS as EngineBLS>::SignatureGroup::zero());
+        assert!( g1_identity.in_subgroup() && g2_identity.in_subgroup() );
+        let g1_bytes = g1_identity.to_bytes();
+        let g2_bytes = g2_identity.to_bytes();
+        assert!( PublicKey::<ZBLS>::from_bytes(&g1_bytes).is_err() );
+        assert!( Signature::<ZBLS>::from_bytes(&g2_bytes).is_err() );
+        assert!( PublicKey::<TBLS>::from_bytes(&g2_bytes).is_err() );
+        assert!( Signature::<TBLS>::from_bytes(&g1_bytes).is_err() );
+        assert!( PublicKey::<ZBLS>::decode::<::codec::ZCashCodec>(&g1_bytes).is_err() );
+        assert!( Signature::<ZBLS>::decode::<::codec::ZCashCodec>(&g2_bytes).is_err() );
+
+        assert!( SecretKeyVT::<ZBLS>::from_bytes(&[0u8; 32]).is_err() );
+        assert!( SecretKey::<ZBLS>::from_bytes(&[0u8; 32]).is_err() );
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert!( SecretKeyVT::<ZBLS>::from_bytes(&one).is_ok() );
+        for _ in 0..16 {
+            assert!( ! SecretKey::<ZBLS>::generate_dirty(thread_rng()).into_vartime().0.is_zero() );
+        }
+    }
+
+    #[test]
+    fn corrupted_point_bytes() {
+        let context = Context::new(b"ctx");
+        let message = b"test message";
+
+        let mut keypair = Keypair::<ZBLS>::generate(thread_rng());
+        let good = zbls_usual_bytes_test(keypair.sign(&context,message));
+        assert!( good.verify() );
+        let mut publickey = good.publickey.to_bytes();
+        let mut signature = good.signature.to_bytes();
+        assert!( publickey.len() == 48 && signature.len() == 96 );
+        publickey[20] ^= 0x10;
+        signature[60] ^= 0x10;
+        assert!( PublicKey::<ZBLS>::from_bytes(&publickey).is_err() );
+        assert!( Signature::<ZBLS>::from_bytes(&signature).is_err() );
+        assert!( PublicKey::<ZBLS>::from_bytes(&[0xffu8; 48]).is_err() );
+        assert!( Signature::<ZBLS>::from_bytes(&[0xffu8; 96]).is_err() );
+
+        let mut keypair = Keypair::<TBLS>::generate(thread_rng());
+        let good = zbls_tiny_bytes_test(keypair.sign(&context,message));
+        assert!( good.verify() );
+        let mut publickey = good.publickey.to_bytes();
+        let mut signature = good.signature.to_bytes();
+        assert!( publickey.len() == 96 && signature.len() == 48 );
+        publickey[60] ^= 0x10;
+        signature[20] ^= 0x10;
+        assert!( PublicKey::<TBLS>::from_bytes(&publickey).is_err() );
+        assert!( Signature::<TBLS>::from_bytes(&signature).is_err() );
+        assert!( PublicKey::<TBLS>::from_bytes(&[0xffu8; 96]).is_err() );
+        assert!( Signature::<TBLS>::from_bytes(&[0xffu8; 48]).is_err() );
+    }
+
     #[test]
     fn single_messages() {
-        let good = Message::new(b"ctx",b"test message");
+        let context = Context::new(b"ctx");
+        let good = b"test message";
 
         let mut keypair  = Keypair::<ZBLS>::generate(thread_rng());
-        let good_sig = zbls_usual_bytes_test(keypair.sign(good));
+        let good_sig = zbls_usual_bytes_test(keypair.sign(&context,good));
         assert!(good_sig.verify_slow());
 
         let keypair_vt = keypair.into_vartime();
         assert!( keypair_vt.secret.0 == keypair_vt.into_split(thread_rng()).into_vartime().secret.0 );
-        assert!( good_sig == keypair.sign(good) );
-        assert!( good_sig == keypair_vt.sign(good) );
+        assert!( good_sig == keypair.sign(&context,good) );
+        assert!( good_sig == keypair_vt.sign(&context,good) );
 
-        let bad = Message::new(b"ctx",b"wrong message");
-        let bad_sig = zbls_usual_bytes_test(keypair.sign(bad));
-        assert!( bad_sig == keypair.into_vartime().sign(bad) );
+        let bad = b"wrong message";
+        let bad_sig = zbls_usual_bytes_test(keypair.sign(&context,bad));
+        assert!( bad_sig == keypair.into_vartime().sign(&context,bad) );
         assert!( bad_sig.verify() );
 
-        let another = Message::new(b"ctx",b"another message");
-        let another_sig = keypair.sign(another);
-        assert!( another_sig == keypair.into_vartime().sign(another) );
+        let another = b"another message";
+        let another_sig = keypair.sign(&context,another);
+        assert!( another_sig == keypair.into_vartime().sign(&context,another) );
         assert!( another_sig.verify() );
 
-        assert!(keypair.public.verify(good, &good_sig.signature),
+        assert!(keypair.public.verify(&context, good, &good_sig.signature),
                 "Verification of a valid signature failed!");
 
-        assert!(!keypair.public.verify(good, &bad_sig.signature),
+        assert!(!keypair.public.verify(&context, good, &bad_sig.signature),
                 "Verification of a signature on a different message passed!");
-        assert!(!keypair.public.verify(bad, &good_sig.signature),
+        assert!(!keypair.public.verify(&context, bad, &good_sig.signature),
                 "Verification of a signature on a different message passed!");
-        assert!(!keypair.public.verify(Message::new(b"other",b"test message"), &good_sig.signature),
+        assert!(!keypair.public.verify(&Context::new(b"other"), good, &good_sig.signature),
                 "Verification of a signature on a different message passed!");
     }
+
+    #[test]
+    fn contexts() {
+        let alice = Context::new(b"alice protocol");
+        let bob = Context::new(b"bob protocol");
+        let mut keypair = Keypair::<ZBLS>::generate(thread_rng());
+
+        let signed = keypair.sign(&alice,b"test message");
+        assert!( signed.verify() );
+        assert!( signed.message == Message::new(&alice,b"test message") );
+        assert!( keypair.public.verify(&alice.clone(), b"test message", &signed.signature) );
+        assert!( keypair.public.verify(&Context::new(b"alice protocol"), b"test message", &signed.signature) );
+        assert!( ! keypair.public.verify(&bob, b"test message", &signed.signature) );
+        assert!( ! signed.signature.verify(&bob, b"test message", &keypair.public) );
+        assert!( ! keypair.into_vartime().secret.sign(&bob, b"test message").verify(&alice, b"test message", &keypair.public) );
+        assert!( format!("{:?}", alice) == format!("{:?}", alice.clone()) );
+        assert!( format!("{:?}", alice) != format!("{:?}", bob) );
+
+        // `From<&[u8]>` uses the empty context
+        let message: Message = (&b"test message"[..]).into();
+        assert!( message == Message::new(&Context::new(b""),b"test message") );
+        assert!( message != Message::new(&alice,b"test message") );
+    }
 }